@@ -0,0 +1,194 @@
+use std::{fs, path::{Path, PathBuf}, time::SystemTime};
+
+use math::round;
+use serde_derive::Serialize;
+
+use crate::{input::REPORT_NAME, ipa_report::SweepReport};
+
+///Every export under `root`: Report.xml files and zips, in path order so runs are repeatable.
+///`skip` is left out, so an output directory inside the tree isn't read back in.
+pub fn discover(root: &Path, skip: Option<&Path>) -> Result<Vec<PathBuf>, String> {
+
+    let mut found: Vec<PathBuf> = Vec::new();
+    let mut pending: Vec<PathBuf> = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+
+        let entries = fs::read_dir(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+
+            if skip.is_some_and(|skip| path == skip) {
+                continue;
+            }
+
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.file_name().is_some_and(|name| name == REPORT_NAME) || is_zip(&path) {
+                found.push(path);
+            }
+        }
+    }
+
+    found.sort();
+    Ok(found)
+}
+
+///Size and modification time of an export, to tell when it has changed. A Report.xml export
+///covers its whole folder, as the CSVs can finish uploading after Report.xml.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fingerprint {
+    pub files: usize,
+    pub bytes: u64,
+    pub modified: Option<SystemTime>,
+}
+
+pub fn fingerprint(export: &Path) -> Result<Fingerprint, String> {
+
+    let files: Vec<PathBuf> = match is_zip(export) {
+        true => vec![export.to_path_buf()],
+        false => {
+            let dir = export.parent().unwrap_or(Path::new("."));
+            fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .filter(|path| path.is_file())
+                .collect()
+        },
+    };
+
+    let mut fingerprint = Fingerprint { files: files.len(), bytes: 0, modified: None };
+
+    for file in files {
+        let metadata = fs::metadata(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+        fingerprint.bytes += metadata.len();
+        fingerprint.modified = fingerprint.modified.max(metadata.modified().ok());
+    }
+
+    Ok(fingerprint)
+}
+
+fn is_zip(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("zip"))
+}
+
+///Exports are kept in a folder per site, so the site is the first folder under the root. Zips
+///sitting directly in the root are named after themselves.
+pub fn site_of(root: &Path, export: &Path) -> String {
+
+    let relative = export.strip_prefix(root).unwrap_or(export);
+    let mut components = relative.components();
+
+    match (components.next(), components.next()) {
+        (Some(site), Some(_)) => site.as_os_str().to_string_lossy().to_string(),
+        //Report.xml in the root itself
+        (Some(_), None) if export.file_name().is_some_and(|name| name == REPORT_NAME) => {
+            root.file_name().map_or("export".to_string(), |e| e.to_string_lossy().to_string())
+        },
+        _ => export.file_stem().map_or("export".to_string(), |e| e.to_string_lossy().to_string()),
+    }
+}
+
+///Where an export's outputs go under the batch output: `<site>/<path under the site folder>`.
+///Zips keep their extension, so `a.zip` and `a/Report.xml` side by side don't share a folder.
+pub fn output_dir(root: &Path, export: &Path) -> PathBuf {
+
+    let site = site_of(root, export);
+    let site_root = root.join(&site);
+
+    //exports in the root are a site of their own
+    if !site_root.is_dir() || !export.starts_with(&site_root) {
+        return match is_zip(export) {
+            true => PathBuf::from(export.file_name().map_or(site, |e| e.to_string_lossy().to_string())),
+            false => PathBuf::from(site),
+        };
+    }
+
+    let relative = export.strip_prefix(&site_root).unwrap_or(export);
+    let relative = match relative.file_name().is_some_and(|name| name == REPORT_NAME) {
+        true => relative.parent().unwrap_or(Path::new("")).to_path_buf(),
+        false => relative.to_path_buf(),
+    };
+
+    let name = relative.components().map(|e| e.as_os_str().to_string_lossy().to_string()).collect::<Vec<String>>().join("_");
+    match name.is_empty() {
+        true => PathBuf::from(site),
+        false => PathBuf::from(site).join(name),
+    }
+}
+
+///One export in the roll-up index.
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexRow {
+    pub site: String,
+    pub path: String,
+    ///date of the first test
+    pub date: Option<String>,
+    pub cables: usize,
+    pub total_length_m: f64,
+    pub failures: usize,
+    pub warnings: usize,
+    ///where the export's outputs were written, relative to the batch output
+    pub output: Option<String>,
+    ///why the export couldn't be processed
+    pub error: Option<String>,
+}
+
+impl IndexRow {
+    pub fn from_sweep_report(site: String, path: String, input: &SweepReport, warnings: usize, output: String) -> IndexRow {
+        IndexRow {
+            site,
+            path,
+            date: input.reports.iter().filter_map(|report| report.time()).min().map(|e| e.date_naive().to_string()),
+            cables: input.reports.len(),
+            total_length_m: round::half_away_from_zero(input.reports.iter().filter_map(|report| report.dtf_marker).sum(), 2),
+            failures: input.reports.iter().filter(|report| report.passed() == Some(false)).count(),
+            warnings,
+            output: Some(output),
+            error: None,
+        }
+    }
+
+    pub fn from_error(site: String, path: String, error: String) -> IndexRow {
+        IndexRow { site, path, date: None, cables: 0, total_length_m: 0.0, failures: 0, warnings: 0, output: None, error: Some(error) }
+    }
+}
+
+///Writes index.json and index.csv into `dir`, returning the paths written.
+pub fn write_index(rows: &[IndexRow], dir: &Path) -> Result<Vec<PathBuf>, String> {
+
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+    let json_path = dir.join("index.json");
+    let json = serde_json::to_string_pretty(rows).map_err(|e| e.to_string())?;
+    fs::write(&json_path, json).map_err(|e| e.to_string())?;
+
+    let csv_path = dir.join("index.csv");
+    let mut writer = csv::Writer::from_path(&csv_path).map_err(|e| e.to_string())?;
+    for row in rows {
+        writer.serialize(row).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+
+    Ok(vec![json_path, csv_path])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zips_and_folders_of_the_same_name_get_their_own_output() {
+        let root = std::env::temp_dir().join(format!("kaelus_batch_{}", std::process::id()));
+        fs::create_dir_all(root.join("site/a")).unwrap();
+
+        let folder = output_dir(&root, &root.join("site/a").join(REPORT_NAME));
+        let zip = output_dir(&root, &root.join("site/a.zip"));
+        let top = output_dir(&root, &root.join("site.zip"));
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(folder, Path::new("site/a"));
+        assert_eq!(zip, Path::new("site/a.zip"));
+        assert_eq!(top, Path::new("site.zip"));
+    }
+}
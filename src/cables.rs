@@ -0,0 +1,32 @@
+///A feeder type the tester can be set to.
+#[derive(Debug, Clone)]
+pub struct CableType {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub velocity_factor: f64,
+    ///attenuation around 2 GHz
+    pub loss_db_per_m: f64,
+}
+
+///Nominal datasheet values, check the datasheet for the exact part and band when it matters.
+pub const CATALOGUE: [CableType; 6] = [
+    CableType { name: "LDF4-50A", description: "1/2\" Heliax", velocity_factor: 0.88, loss_db_per_m: 0.106 },
+    CableType { name: "FSJ4-50B", description: "1/2\" Superflex", velocity_factor: 0.81, loss_db_per_m: 0.178 },
+    CableType { name: "LDF5-50A", description: "7/8\" Heliax", velocity_factor: 0.89, loss_db_per_m: 0.061 },
+    CableType { name: "LCF78-50JA", description: "7/8\" Cellflex", velocity_factor: 0.89, loss_db_per_m: 0.060 },
+    CableType { name: "LDF6-50", description: "1-1/4\" Heliax", velocity_factor: 0.89, loss_db_per_m: 0.044 },
+    CableType { name: "LMR-400", description: "LMR-400 coax", velocity_factor: 0.85, loss_db_per_m: 0.197 },
+];
+
+pub fn find(name: &str) -> Option<&'static CableType> {
+    CATALOGUE.iter().find(|cable| cable.name.eq_ignore_ascii_case(name.trim()))
+}
+
+///The catalogue cable a DTF setup was most likely for: the same VF and a loss within a quarter
+///of the datasheet value.
+pub fn identify(velocity_factor: f64, loss_db_per_m: f64) -> Option<&'static CableType> {
+    CATALOGUE.iter()
+        .filter(|cable| (cable.velocity_factor - velocity_factor).abs() < 0.005)
+        .filter(|cable| (cable.loss_db_per_m - loss_db_per_m).abs() <= cable.loss_db_per_m * 0.25)
+        .min_by(|a, b| (a.loss_db_per_m - loss_db_per_m).abs().total_cmp(&(b.loss_db_per_m - loss_db_per_m).abs()))
+}
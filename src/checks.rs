@@ -0,0 +1,165 @@
+use chrono::Duration;
+
+use crate::{dtf, ipa_report::{SweepReport, Warning}};
+
+///Warns for every DTF/RL result whose field calibration is older than `max_age`, or was done on an earlier day than the test.
+///
+///The export has no record of power cycles or temperature, so a calibration carried over a day boundary
+///is the closest thing we can flag for "the tester was switched off since it was calibrated".
+pub fn field_calibration_age(input: &SweepReport, max_age: Duration) -> Vec<Warning> {
+
+    let mut warnings: Vec<Warning> = Vec::new();
+
+    for report in &input.reports {
+
+        for (name, result) in report.results() {
+
+            if result.calibration_age() > max_age {
+                warnings.push(Warning { 
+                    message: format!("Field calibration too old on {} {}", report.tag, name), 
+                    expected: format!("<{}", format_hours(max_age)), 
+                    result: format_hours(result.calibration_age()) });
+            }
+
+            if result.calibrated_at().date_naive() != result.time.date_naive() {
+                warnings.push(Warning { 
+                    message: format!("Field calibration done before day boundary on {} {}", report.tag, name), 
+                    expected: result.time.date_naive().to_string(), 
+                    result: result.calibrated_at().date_naive().to_string() });
+            }
+        }
+    }
+
+    warnings
+}
+
+///Warns for every device whose factory calibration interval had run out at the time of a test it ran.
+///
+///Tests that don't list their devices are treated as run by every device in the export.
+pub fn factory_calibration(input: &SweepReport, interval_months: u32) -> Vec<Warning> {
+
+    let mut warnings: Vec<Warning> = Vec::new();
+
+    for device in &input.devices {
+
+        let expired: Vec<_> = input.reports.iter()
+            .flat_map(|report| report.results())
+            .filter(|(_, result)| input.devices_for_test(result).iter().any(|e| e.ID == device.ID))
+            .map(|(_, result)| result.time)
+            .filter(|time| !device.calibration_valid_at(time, interval_months))
+            .collect();
+
+        if let Some(latest) = expired.iter().max() {
+            warnings.push(Warning { 
+                message: format!("Factory calibration expired on device {} for {} tests", device.serial_number, expired.len()), 
+                expected: format!("<{}", device.calibration_due(interval_months).date_naive()), 
+                result: latest.date_naive().to_string() });
+        }
+    }
+
+    warnings
+}
+
+///Warns when the DTF marker is more than `tolerance` metres from the cable end estimated from the trace,
+///which usually means the marker was never moved to the end of the cable.
+pub fn marker_position(input: &SweepReport, tolerance: f64) -> Vec<Warning> {
+
+    let mut warnings: Vec<Warning> = Vec::new();
+
+    for report in &input.reports {
+
+        let (Some(trace), Some(marker)) = (&report.dtf_trace, report.dtf_marker) else { continue };
+        let Some(estimate) = dtf::estimate_cable_end(trace, &dtf::EventSettings::default()) else { continue };
+
+        if (estimate - marker).abs() > tolerance {
+            warnings.push(Warning { 
+                message: format!("Marker disagrees with estimated cable end on {}", report.tag), 
+                expected: format!("{:.2}m", estimate), 
+                result: format!("{:.2}m", marker) });
+        }
+    }
+
+    warnings
+}
+
+fn format_hours(elapsed: Duration) -> String {
+    format!("{}h{:0>2}m", elapsed.num_hours(), elapsed.num_minutes() % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveTime, TimeZone};
+    use chrono_tz::Tz;
+
+    use super::*;
+    use crate::ipa_report::{self, Device};
+
+    fn fixture() -> SweepReport {
+        ipa_report::fixture_report(Tz::UTC).0
+    }
+
+    //the fixture's tester was out of calibration, this is the same export after a recalibration
+    fn valid_cal_fixture() -> SweepReport {
+        let mut report = fixture();
+        report.devices[0].calibration_date = Tz::UTC.with_ymd_and_hms(2022, 9, 18, 2, 38, 30).unwrap();
+        report
+    }
+
+    #[test]
+    fn warns_on_the_fixture_calibration_age() {
+        let warnings = field_calibration_age(&fixture(), Duration::hours(8));
+
+        assert_eq!(warnings.len(), 10);
+        assert_eq!(warnings[0].message, "Field calibration too old on F-L29-226 DTF");
+        assert_eq!(warnings[0].result, "9h28m");
+        assert!(field_calibration_age(&fixture(), Duration::hours(10)).is_empty());
+    }
+
+    #[test]
+    fn flags_a_calibration_from_before_midnight() {
+        let mut report = fixture();
+        let result = report.reports[0].dtf_result.as_mut().unwrap();
+        //tested at 13:30, so 22:15 was the evening before
+        result.calibrated = NaiveTime::from_hms_opt(22, 15, 0).unwrap();
+
+        let warnings = field_calibration_age(&report, Duration::hours(24));
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "Field calibration done before day boundary on F-L29-226 DTF");
+        assert_eq!(warnings[0].expected, "2023-03-20");
+        assert_eq!(warnings[0].result, "2023-03-19");
+
+        let warnings = field_calibration_age(&report, Duration::hours(8));
+        assert_eq!(warnings[0].result, "15h15m");
+    }
+
+    #[test]
+    fn warns_on_an_expired_factory_calibration() {
+        let warnings = factory_calibration(&fixture(), 12);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "Factory calibration expired on device IE118500 for 10 tests");
+        assert_eq!(warnings[0].expected, "<2021-03-18");
+        assert!(factory_calibration(&valid_cal_fixture(), 12).is_empty());
+    }
+
+    #[test]
+    fn only_checks_devices_against_the_tests_they_ran() {
+        let mut report = valid_cal_fixture();
+        let expired = fixture().devices.remove(0);
+        report.devices.push(Device { serial_number: "IE000001".to_string(), ID: "2".to_string(), ..expired });
+        for cable in &mut report.reports {
+            for result in [&mut cable.dtf_result, &mut cable.rl_result].into_iter().flatten() {
+                result.device_ids = vec!["1".to_string()];
+            }
+        }
+
+        assert!(factory_calibration(&report, 12).is_empty());
+
+        report.reports[0].rl_result.as_mut().unwrap().device_ids = vec!["2".to_string()];
+        let warnings = factory_calibration(&report, 12);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "Factory calibration expired on device IE000001 for 1 tests");
+    }
+}
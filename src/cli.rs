@@ -0,0 +1,392 @@
+use argh::FromArgs;
+
+use crate::{color, database, metrics, render, sort, table};
+
+#[derive(FromArgs)]
+///Parser for Kaelus Sweep Tester Reports.
+pub struct Arguments {
+    ///timezone of the site the tester was used at, e.g. Australia/Sydney (default: $KAELUS_TZ or UTC)
+    #[argh(option)]
+    pub tz: Option<String>,
+    ///colour the output: never, always or auto, which colours only a terminal and honours $NO_COLOR (default: auto)
+    #[argh(option, default = "color::ColorMode::Auto")]
+    pub color: color::ColorMode,
+    ///leave out the Start/Parsed/Processed timings
+    #[argh(switch, short = 'q')]
+    pub quiet: bool,
+    ///exit with status 4 when any warnings were produced
+    #[argh(switch)]
+    pub strict: bool,
+    ///threads to read traces and batch exports on, at least 1 (default: one per core)
+    #[argh(option, short = 'j')]
+    pub jobs: Option<usize>,
+    ///warn when a field calibration is older than this many hours (default: 8)
+    #[argh(option, default = "8")]
+    pub max_cal_age: i64,
+    ///warn when the DTF marker is further than this many metres from the estimated cable end (default: 0.5)
+    #[argh(option, default = "0.5")]
+    pub marker_tolerance: f64,
+    ///factory calibration interval of the test equipment in months (default: 12)
+    #[argh(option, default = "12")]
+    pub cal_interval: u32,
+    ///exit with an error when any device was out of factory calibration
+    #[argh(switch)]
+    pub fail_on_expired_cal: bool,
+    ///rescale DTF distances to this velocity factor, or the VF of a cable type such as LDF4-50A
+    #[argh(option)]
+    pub recompute_vf: Option<String>,
+    ///with --recompute-vf, re-apply this cable loss in dB/m (default: the cable type's, or unchanged)
+    #[argh(option)]
+    pub cable_loss: Option<f64>,
+    ///with --recompute-vf, only recompute these comma separated tags (default: all)
+    #[argh(option)]
+    pub vf_tags: Option<String>,
+    #[argh(subcommand)]
+    pub command: Command,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+pub enum Command {
+    Summary(SummaryCommand),
+    Detail(DetailCommand),
+    Export(ExportCommand),
+    Validate(ValidateCommand),
+    Reconcile(ReconcileCommand),
+    Diff(DiffCommand),
+    Batch(BatchCommand),
+    Watch(WatchCommand),
+    Ingest(IngestCommand),
+    Query(QueryCommand),
+    Serve(ServeCommand),
+    Plot(PlotCommand),
+    Verify(VerifyCommand),
+    CheckManifest(CheckManifestCommand),
+    Events(EventsCommand),
+    Render(RenderCommand),
+    Compare(CompareCommand),
+}
+
+#[derive(FromArgs)]
+///Prints one line per cable with its length, VSWR and RL, after the check warnings.
+#[argh(subcommand, name = "summary")]
+pub struct SummaryCommand {
+    ///export to read: Report.xml, its directory or the zip from the tester
+    #[argh(positional)]
+    pub path: String,
+    ///columns to show, comma separated from tag, length, vswr, vswr_at, rl, pass, time, device and margin (default: tag,length,vswr,rl)
+    #[argh(option)]
+    pub columns: Option<table::Columns>,
+    ///print only the table, in Markdown
+    #[argh(switch)]
+    pub markdown: bool,
+    ///sort the cables by tag, same as --sort-by tag
+    #[argh(switch, short = 's')]
+    pub sort: bool,
+    ///sort the cables by tag, length, vswr, rl, time or margin, comma separated to break ties (e.g. margin,tag)
+    #[argh(option)]
+    pub sort_by: Option<sort::SortKeys>,
+    ///with --sort-by, largest first
+    #[argh(switch)]
+    pub desc: bool,
+    ///show results as vswr, rl, rho or ml (mismatch loss) instead of the unit each test was measured in
+    #[argh(option)]
+    pub unit: Option<metrics::Unit>,
+}
+
+#[derive(FromArgs)]
+///Prints every test of every cable: worst point, time since field calibration, test time and a sparkline of the trace.
+#[argh(subcommand, name = "detail")]
+pub struct DetailCommand {
+    ///export to read: Report.xml, its directory or the zip from the tester
+    #[argh(positional)]
+    pub path: String,
+    ///sort the cables by tag, same as --sort-by tag
+    #[argh(switch, short = 's')]
+    pub sort: bool,
+    ///sort the cables by tag, length, vswr, rl, time or margin, comma separated to break ties (e.g. margin,tag)
+    #[argh(option)]
+    pub sort_by: Option<sort::SortKeys>,
+    ///with --sort-by, largest first
+    #[argh(switch)]
+    pub desc: bool,
+    ///show results as vswr, rl, rho or ml (mismatch loss) instead of the unit each test was measured in
+    #[argh(option)]
+    pub unit: Option<metrics::Unit>,
+}
+
+#[derive(FromArgs)]
+///Writes report.json, report.csv and certificate.html, optionally as a signed package.
+#[argh(subcommand, name = "export")]
+pub struct ExportCommand {
+    ///export to read: Report.xml, its directory or the zip from the tester
+    #[argh(positional)]
+    pub path: String,
+    ///directory to write into (default: export)
+    #[argh(option, default = "String::from(\"export\")")]
+    pub out: String,
+    ///order the cables by tag, length, vswr, rl, time or margin, comma separated to break ties (default: report order)
+    #[argh(option)]
+    pub sort_by: Option<sort::SortKeys>,
+    ///with --sort-by, largest first
+    #[argh(switch)]
+    pub desc: bool,
+    ///also copy in Report.xml and its assets and write a MANIFEST.sha256 of the package
+    #[argh(switch)]
+    pub manifest: bool,
+    ///with --manifest, sign the manifest with the HMAC key in this file
+    #[argh(option)]
+    pub sign_key: Option<String>,
+    ///also render a chart of every trace into plots/ as svg or png, listed in the manifest
+    #[argh(option)]
+    pub charts: Option<render::Format>,
+}
+
+#[derive(FromArgs)]
+///Runs the parse and calibration checks and exits with an error if any of them warn or a test failed.
+#[argh(subcommand, name = "validate")]
+pub struct ValidateCommand {
+    ///export to read: Report.xml, its directory or the zip from the tester
+    #[argh(positional)]
+    pub path: String,
+}
+
+#[derive(FromArgs)]
+///Checks an export against the cable schedule: missing, failed, unexpected and wrong length cables.
+#[argh(subcommand, name = "reconcile")]
+pub struct ReconcileCommand {
+    ///export to read: Report.xml, its directory or the zip from the tester
+    #[argh(positional)]
+    pub path: String,
+    ///cable schedule CSV with a tag column and optionally a length column in metres
+    #[argh(positional)]
+    pub schedule: String,
+    ///flag cables whose measured length is further than this many metres from the schedule (default: 1.0)
+    #[argh(option, default = "1.0")]
+    pub length_tolerance: f64,
+    ///print the reconciliation as JSON
+    #[argh(switch)]
+    pub json: bool,
+}
+
+#[derive(FromArgs)]
+///Watches a drop folder for new and changed exports, keeping a progress report against the cable schedule up to date.
+#[argh(subcommand, name = "watch")]
+pub struct WatchCommand {
+    ///directory the exports are dropped into, searched like batch
+    #[argh(positional)]
+    pub path: String,
+    ///cable schedule CSV with a tag column and optionally a length column in metres
+    #[argh(positional)]
+    pub schedule: String,
+    ///directory to write progress.json and progress.html into (default: progress)
+    #[argh(option, default = "String::from(\"progress\")")]
+    pub out: String,
+    ///seconds between checks of the folder (default: 5)
+    #[argh(option, default = "5")]
+    pub interval: u64,
+    ///flag cables whose measured length is further than this many metres from the schedule (default: 1.0)
+    #[argh(option, default = "1.0")]
+    pub length_tolerance: f64,
+    ///check the folder once, write the progress report and exit as reconcile would
+    #[argh(switch)]
+    pub once: bool,
+}
+
+#[derive(FromArgs)]
+///Stores exports in a SQLite results database, replacing tests that are already in it.
+#[argh(subcommand, name = "ingest")]
+pub struct IngestCommand {
+    ///database file, created if it doesn't exist
+    #[argh(positional)]
+    pub database: String,
+    ///export to read, or a directory searched like batch with the first folder under it as the site
+    #[argh(positional)]
+    pub path: String,
+    ///site the exports are from (default: the first folder under a directory, or the export's folder)
+    #[argh(option)]
+    pub site: Option<String>,
+}
+
+#[derive(FromArgs)]
+///Answers a question from the results database: failures, retests or cables (totals by cable type).
+#[argh(subcommand, name = "query")]
+pub struct QueryCommand {
+    ///database file written by ingest
+    #[argh(positional)]
+    pub database: String,
+    ///failures, retests or cables
+    #[argh(positional)]
+    pub question: database::Question,
+    ///only this site
+    #[argh(option)]
+    pub site: Option<String>,
+    ///print the answer as JSON
+    #[argh(switch)]
+    pub json: bool,
+    ///print the answer as a Markdown table
+    #[argh(switch)]
+    pub markdown: bool,
+}
+
+#[derive(FromArgs)]
+///Serves a web viewer and JSON API over exports, on this machine only unless --bind says otherwise.
+#[argh(subcommand, name = "serve")]
+pub struct ServeCommand {
+    ///export to serve, or a directory searched like batch with the first folder under it as the site
+    #[argh(positional)]
+    pub path: String,
+    ///port to listen on (default: 8080)
+    #[argh(option, default = "8080")]
+    pub port: u16,
+    ///address to listen on, 0.0.0.0 to let other machines connect (default: 127.0.0.1)
+    #[argh(option, default = "String::from(\"127.0.0.1\")")]
+    pub bind: String,
+}
+
+#[derive(FromArgs)]
+///Processes every export under a directory tree, one site per top level folder, into per-site outputs and a roll-up index.
+#[argh(subcommand, name = "batch")]
+pub struct BatchCommand {
+    ///directory to search for Report.xml files and zips
+    #[argh(positional)]
+    pub path: String,
+    ///directory to write the index and per-site outputs into (default: batch)
+    #[argh(option, default = "String::from(\"batch\")")]
+    pub out: String,
+}
+
+#[derive(FromArgs)]
+///Checks the device signatures in a report and prints the report digest.
+#[argh(subcommand, name = "verify")]
+pub struct VerifyCommand {
+    ///export to read: Report.xml, its directory or the zip from the tester
+    #[argh(positional)]
+    pub path: String,
+}
+
+#[derive(FromArgs)]
+///Checks a delivered package against its MANIFEST.sha256.
+#[argh(subcommand, name = "check-manifest")]
+pub struct CheckManifestCommand {
+    ///directory holding MANIFEST.sha256
+    #[argh(positional)]
+    pub path: String,
+    ///file holding the HMAC key the manifest was signed with
+    #[argh(option)]
+    pub key: Option<String>,
+}
+
+#[derive(FromArgs)]
+///Lists the reflection events found in each DTF trace.
+#[argh(subcommand, name = "events")]
+pub struct EventsCommand {
+    ///export to read: Report.xml, its directory or the zip from the tester
+    #[argh(positional)]
+    pub path: String,
+    ///only show this tag
+    #[argh(option)]
+    pub tag: Option<String>,
+    ///smallest VSWR peak to report (default: 1.05)
+    #[argh(option, default = "1.05")]
+    pub threshold: f64,
+    ///VSWR at which a peak along the line is flagged as possible damage (default: the DTF limit)
+    #[argh(option)]
+    pub damage: Option<f64>,
+}
+
+#[derive(FromArgs)]
+///Draws the DTF and RL traces of one cable in the terminal.
+#[argh(subcommand, name = "plot")]
+pub struct PlotCommand {
+    ///export to read: Report.xml, its directory or the zip from the tester
+    #[argh(positional)]
+    pub path: String,
+    ///tag of the cable to plot
+    #[argh(positional)]
+    pub tag: String,
+    ///chart width in characters (default: 72)
+    #[argh(option, default = "72")]
+    pub width: usize,
+    ///chart height in characters (default: 12)
+    #[argh(option, default = "12")]
+    pub height: usize,
+}
+
+#[derive(FromArgs)]
+///Renders an SVG or PNG chart of every DTF and RL trace for client reports.
+#[argh(subcommand, name = "render")]
+pub struct RenderCommand {
+    ///export to read: Report.xml, its directory or the zip from the tester
+    #[argh(positional)]
+    pub path: String,
+    ///directory to write the charts into (default: plots)
+    #[argh(option, default = "String::from(\"plots\")")]
+    pub out: String,
+    ///svg or png (default: svg)
+    #[argh(option, default = "render::Format::Svg")]
+    pub format: render::Format,
+    ///only render this tag
+    #[argh(option)]
+    pub tag: Option<String>,
+}
+
+#[derive(FromArgs)]
+///Overlays the traces of two cables, or two attempts at one, and reports how far apart they are.
+#[argh(subcommand, name = "compare")]
+pub struct CompareCommand {
+    ///export to read: Report.xml, its directory or the zip from the tester
+    #[argh(positional)]
+    pub path: String,
+    ///tag of the first cable
+    #[argh(positional)]
+    pub tag_a: String,
+    ///tag of the second cable
+    #[argh(positional)]
+    pub tag_b: String,
+    ///read the second cable from this export instead, e.g. a later attempt
+    #[argh(option)]
+    pub against: Option<String>,
+    ///highlight length differences larger than this many metres (default: 0.5)
+    #[argh(option, default = "0.5")]
+    pub length_tolerance: f64,
+    ///highlight VSWR differences larger than this (default: 0.05)
+    #[argh(option, default = "0.05")]
+    pub vswr_tolerance: f64,
+    ///highlight RL differences larger than this many dB (default: 0.5)
+    #[argh(option, default = "0.5")]
+    pub rl_tolerance: f64,
+    ///chart width in characters (default: 72)
+    #[argh(option, default = "72")]
+    pub width: usize,
+    ///chart height in characters (default: 12)
+    #[argh(option, default = "12")]
+    pub height: usize,
+}
+
+#[derive(FromArgs)]
+///Lists what changed between two exports of the same site: added and removed cables, length, VSWR and RL changes and pass/fail transitions.
+#[argh(subcommand, name = "diff")]
+pub struct DiffCommand {
+    ///earlier export: Report.xml, its directory or the zip from the tester
+    #[argh(positional)]
+    pub old: String,
+    ///later export
+    #[argh(positional)]
+    pub new: String,
+    ///print the differences as JSON
+    #[argh(switch)]
+    pub json: bool,
+    ///ignore length changes smaller than this many metres (default: 0.5)
+    #[argh(option, default = "0.5")]
+    pub length_tolerance: f64,
+    ///ignore VSWR changes smaller than this (default: 0.05)
+    #[argh(option, default = "0.05")]
+    pub vswr_tolerance: f64,
+    ///ignore RL changes smaller than this many dB (default: 0.5)
+    #[argh(option, default = "0.5")]
+    pub rl_tolerance: f64,
+    ///also list the cables that did not change
+    #[argh(switch, short = 'a')]
+    pub all: bool,
+}
@@ -0,0 +1,67 @@
+use std::{fmt::Display, io::{self, IsTerminal}, env, str::FromStr, sync::atomic::{AtomicBool, Ordering}};
+
+use owo_colors::OwoColorize;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static STDERR_ENABLED: AtomicBool = AtomicBool::new(true);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorMode {
+    Never,
+    Always,
+    ///colour each stream when it is a terminal and `NO_COLOR` isn't set
+    Auto,
+}
+
+impl FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.trim().to_ascii_lowercase().as_str() {
+            "never" => Ok(ColorMode::Never),
+            "always" => Ok(ColorMode::Always),
+            "auto" => Ok(ColorMode::Auto),
+            _ => Err(format!("Unknown color mode: {}, expected never, always or auto", input)),
+        }
+    }
+}
+
+pub fn init(mode: ColorMode) {
+    let enabled = |is_terminal: bool| match mode {
+        ColorMode::Never => false,
+        ColorMode::Always => true,
+        //https://no-color.org, set and not empty
+        ColorMode::Auto => env::var_os("NO_COLOR").is_none_or(|e| e.is_empty()) && is_terminal,
+    };
+    ENABLED.store(enabled(io::stdout().is_terminal()), Ordering::Relaxed);
+    STDERR_ENABLED.store(enabled(io::stderr().is_terminal()), Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+///Whether errors written to stderr are coloured, it can be a terminal while stdout is piped.
+pub fn stderr_enabled() -> bool {
+    STDERR_ENABLED.load(Ordering::Relaxed)
+}
+
+fn paint(text: String, style: fn(&String) -> String) -> String {
+    match enabled() {
+        true => style(&text),
+        false => text,
+    }
+}
+
+///The owo-colors styles used for terminal output, switched off by `--color never` or when piped.
+pub trait Colorize: Display {
+    fn green(&self) -> String { paint(self.to_string(), |e| OwoColorize::green(e).to_string()) }
+    fn red(&self) -> String { paint(self.to_string(), |e| OwoColorize::red(e).to_string()) }
+    fn yellow(&self) -> String { paint(self.to_string(), |e| OwoColorize::yellow(e).to_string()) }
+    fn blue(&self) -> String { paint(self.to_string(), |e| OwoColorize::blue(e).to_string()) }
+    fn cyan(&self) -> String { paint(self.to_string(), |e| OwoColorize::cyan(e).to_string()) }
+    fn bold(&self) -> String { paint(self.to_string(), |e| OwoColorize::bold(e).to_string()) }
+    fn underline(&self) -> String { paint(self.to_string(), |e| OwoColorize::underline(e).to_string()) }
+}
+
+impl<T: Display + ?Sized> Colorize for T {}
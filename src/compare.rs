@@ -0,0 +1,108 @@
+use serde_derive::Serialize;
+
+use crate::{ipa_report::Report, trace::Trace};
+
+///Largest difference between two traces over the range they share.
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceDelta {
+    ///`b - a` at the point of largest difference
+    pub max_difference: f64,
+    ///distance or frequency where it occurs
+    pub at: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Comparison {
+    pub length: (Option<f64>, Option<f64>),
+    pub vswr: (Option<f64>, Option<f64>),
+    pub rl: (Option<f64>, Option<f64>),
+    pub dtf_trace: Option<TraceDelta>,
+    pub rl_trace: Option<TraceDelta>,
+}
+
+impl Comparison {
+    pub fn length_delta(&self) -> Option<f64> {
+        delta(self.length)
+    }
+
+    pub fn vswr_delta(&self) -> Option<f64> {
+        delta(self.vswr)
+    }
+
+    pub fn rl_delta(&self) -> Option<f64> {
+        delta(self.rl)
+    }
+}
+
+fn delta(values: (Option<f64>, Option<f64>)) -> Option<f64> {
+    Some(values.1? - values.0?)
+}
+
+///Compares cable `b` against cable `a`: two feeders that should be identical, or two attempts at the same one.
+pub fn compare(a: &Report, b: &Report) -> Comparison {
+    Comparison { 
+        length: (a.dtf_marker, b.dtf_marker), 
+        vswr: (a.dtf_result.as_ref().map(|e| e.max.1), b.dtf_result.as_ref().map(|e| e.max.1)), 
+        rl: (a.rl_result.as_ref().map(|e| e.max.1), b.rl_result.as_ref().map(|e| e.max.1)), 
+        dtf_trace: trace_delta(a.dtf_trace.as_ref(), b.dtf_trace.as_ref()), 
+        rl_trace: trace_delta(a.rl_trace.as_ref(), b.rl_trace.as_ref()),
+    }
+}
+
+pub fn trace_delta(a: Option<&Trace>, b: Option<&Trace>) -> Option<TraceDelta> {
+
+    let (a, b) = (a?, b?);
+    let mut largest: Option<TraceDelta> = None;
+
+    for (x, y) in &a.points {
+        let Some(other) = b.value_at(*x) else { continue };
+        let difference = other - y;
+
+        if largest.as_ref().is_none_or(|e| difference.abs() > e.max_difference.abs()) {
+            largest = Some(TraceDelta { max_difference: difference, at: *x });
+        }
+    }
+
+    largest
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Tz;
+
+    use super::*;
+    use crate::ipa_report::fixture_report;
+
+    #[test]
+    fn a_cable_compared_with_itself_has_no_difference() {
+        let cable = fixture_report(Tz::UTC).0.reports.remove(0);
+        let comparison = compare(&cable, &cable);
+
+        assert_eq!(comparison.length_delta(), Some(0.0));
+        assert_eq!(comparison.vswr_delta(), Some(0.0));
+        assert_eq!(comparison.rl_delta(), Some(0.0));
+        assert_eq!(comparison.dtf_trace.unwrap().max_difference, 0.0);
+    }
+
+    #[test]
+    fn finds_the_largest_difference_and_where_it_is() {
+        let a = fixture_report(Tz::UTC).0.reports.remove(0);
+        let mut b = a.clone();
+        b.dtf_marker = Some(27.5);
+        b.dtf_result.as_mut().unwrap().max.1 = 1.2;
+        let points = &mut b.dtf_trace.as_mut().unwrap().points;
+        points[100].1 -= 0.3;
+        points[200].1 += 0.1;
+
+        let comparison = compare(&a, &b);
+
+        assert!((comparison.length_delta().unwrap() - 0.61).abs() < 1e-9);
+        assert!((comparison.vswr_delta().unwrap() + 0.125).abs() < 1e-9);
+        let delta = comparison.dtf_trace.unwrap();
+        assert!((delta.max_difference + 0.3).abs() < 1e-9);
+        assert_eq!(delta.at, a.dtf_trace.as_ref().unwrap().points[100].0);
+
+        //nothing to compare against
+        assert!(trace_delta(a.dtf_trace.as_ref(), None).is_none());
+    }
+}
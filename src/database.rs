@@ -0,0 +1,417 @@
+use std::{path::Path, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde_derive::Serialize;
+
+use crate::{cables, ipa_report::{Device, Limit, Report, SweepReport, TestResult}, trace::Trace};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS exports (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL UNIQUE,
+        site TEXT NOT NULL,
+        version TEXT NOT NULL,
+        ingested TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS devices (
+        serial_number TEXT PRIMARY KEY,
+        model TEXT NOT NULL,
+        sw_version TEXT NOT NULL,
+        calibration_date TEXT NOT NULL,
+        signature TEXT
+    );
+    CREATE TABLE IF NOT EXISTS states (
+        export_id INTEGER NOT NULL REFERENCES exports(id),
+        state_id TEXT NOT NULL,
+        test_type TEXT NOT NULL,
+        rx_start_khz INTEGER,
+        rx_stop_khz INTEGER,
+        points INTEGER NOT NULL,
+        distance_m INTEGER,
+        vf REAL,
+        cable_loss_db_per_m REAL,
+        limit_unit TEXT NOT NULL,
+        limit_value REAL,
+        PRIMARY KEY (export_id, state_id)
+    );
+    CREATE TABLE IF NOT EXISTS tests (
+        device_serial TEXT NOT NULL,
+        test_id TEXT NOT NULL,
+        recorded TEXT NOT NULL,
+        time TEXT NOT NULL,
+        export_id INTEGER NOT NULL REFERENCES exports(id),
+        state_id TEXT,
+        site TEXT NOT NULL,
+        tag TEXT NOT NULL,
+        test_type TEXT NOT NULL,
+        cable_type TEXT,
+        PRIMARY KEY (device_serial, test_id, recorded)
+    );
+    CREATE TABLE IF NOT EXISTS results (
+        device_serial TEXT NOT NULL,
+        test_id TEXT NOT NULL,
+        recorded TEXT NOT NULL,
+        measurement_type TEXT NOT NULL,
+        unit TEXT NOT NULL,
+        max_x REAL NOT NULL,
+        max_y REAL NOT NULL,
+        min_x REAL NOT NULL,
+        min_y REAL NOT NULL,
+        average REAL NOT NULL,
+        ripple REAL NOT NULL,
+        pass INTEGER NOT NULL,
+        marker_m REAL,
+        calibrated_at TEXT NOT NULL,
+        PRIMARY KEY (device_serial, test_id, recorded)
+    );
+    CREATE TABLE IF NOT EXISTS traces (
+        device_serial TEXT NOT NULL,
+        test_id TEXT NOT NULL,
+        recorded TEXT NOT NULL,
+        points TEXT NOT NULL,
+        PRIMARY KEY (device_serial, test_id, recorded)
+    );
+    CREATE INDEX IF NOT EXISTS tests_site_tag ON tests (site, tag);
+";
+
+///Times are stored in UTC so they sort the same whatever timezone each site was in.
+fn format_time(time: &DateTime<Tz>) -> String {
+    time.with_timezone(&Utc).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+///The time as the tester recorded it, which doesn't change with `--tz` like the UTC time does.
+fn format_recorded(time: &DateTime<Tz>) -> String {
+    time.naive_local().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+///How many tests an ingest added and how many were already in the database.
+#[derive(Debug, Default)]
+pub struct Ingested {
+    pub cables: usize,
+    pub new: usize,
+    pub updated: usize,
+}
+
+///The history of every export ingested, kept in a SQLite file.
+pub struct Database {
+    connection: Connection,
+}
+
+impl Database {
+
+    ///Opens `path`, creating the file and tables if they don't exist yet.
+    pub fn open(path: &Path) -> Result<Database, String> {
+        let connection = Connection::open(path).map_err(|e| e.to_string())?;
+        connection.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+        Ok(Database { connection })
+    }
+
+    ///Stores an export in one transaction. A test is identified by the serial of the device that
+    ///ran it, its ID and the time the tester recorded, as the tester starts the IDs from 1 in every
+    ///export, so ingesting the same export again, under any `--tz`, replaces its rows instead of
+    ///duplicating them.
+    pub fn ingest(&mut self, site: &str, path: &str, input: &SweepReport) -> Result<Ingested, String> {
+
+        let transaction = self.connection.transaction().map_err(|e| e.to_string())?;
+        let ingested = ingest_export(&transaction, site, path, input).map_err(|e| e.to_string())?;
+        transaction.commit().map_err(|e| e.to_string())?;
+
+        Ok(ingested)
+    }
+
+    ///Failed tests, each with whether a later test of the same cable passed.
+    pub fn failures(&self, site: Option<&str>) -> Result<Vec<FailedTest>, String> {
+
+        let mut statement = self.connection.prepare("
+            SELECT t.site, t.tag, t.test_type, t.time, t.device_serial, r.max_y, r.unit,
+                EXISTS (
+                    SELECT 1 FROM tests l JOIN results lr USING (device_serial, test_id, recorded)
+                    WHERE l.site = t.site AND l.tag = t.tag AND l.test_type = t.test_type AND l.time > t.time AND lr.pass = 1
+                )
+            FROM tests t JOIN results r USING (device_serial, test_id, recorded)
+            WHERE r.pass = 0 AND (?1 IS NULL OR t.site = ?1)
+            ORDER BY t.site, t.tag, t.time
+        ").map_err(|e| e.to_string())?;
+
+        let rows = statement.query_map(params![site], |row| Ok(FailedTest {
+            site: row.get(0)?,
+            tag: row.get(1)?,
+            test_type: row.get(2)?,
+            time: row.get(3)?,
+            device_serial: row.get(4)?,
+            worst: row.get(5)?,
+            unit: row.get(6)?,
+            passed_later: row.get(7)?,
+        })).map_err(|e| e.to_string())?;
+
+        rows.collect::<Result<Vec<FailedTest>, rusqlite::Error>>().map_err(|e| e.to_string())
+    }
+
+    ///Cables tested more than once with the same test type.
+    pub fn retests(&self, site: Option<&str>) -> Result<Vec<Retest>, String> {
+
+        let mut statement = self.connection.prepare("
+            SELECT site, tag, test_type, COUNT(*), MIN(time), MAX(time),
+                (SELECT r.pass FROM tests l JOIN results r USING (device_serial, test_id, recorded)
+                    WHERE l.site = t.site AND l.tag = t.tag AND l.test_type = t.test_type ORDER BY l.time DESC LIMIT 1)
+            FROM tests t
+            WHERE ?1 IS NULL OR site = ?1
+            GROUP BY site, tag, test_type
+            HAVING COUNT(*) > 1
+            ORDER BY site, tag, test_type
+        ").map_err(|e| e.to_string())?;
+
+        let rows = statement.query_map(params![site], |row| Ok(Retest {
+            site: row.get(0)?,
+            tag: row.get(1)?,
+            test_type: row.get(2)?,
+            attempts: row.get(3)?,
+            first: row.get(4)?,
+            last: row.get(5)?,
+            last_passed: row.get(6)?,
+        })).map_err(|e| e.to_string())?;
+
+        rows.collect::<Result<Vec<Retest>, rusqlite::Error>>().map_err(|e| e.to_string())
+    }
+
+    ///Cable count and length by cable type, from the last DTF test of every cable.
+    pub fn cable_totals(&self, site: Option<&str>) -> Result<Vec<CableTotal>, String> {
+
+        let mut statement = self.connection.prepare("
+            WITH latest AS (
+                SELECT t.cable_type, r.marker_m,
+                    ROW_NUMBER() OVER (PARTITION BY t.site, t.tag ORDER BY t.time DESC) AS attempt
+                FROM tests t JOIN results r USING (device_serial, test_id, recorded)
+                WHERE t.test_type = 'DTF' AND (?1 IS NULL OR t.site = ?1)
+            )
+            SELECT COALESCE(cable_type, 'Unknown'), COUNT(*), SUM(marker_m)
+            FROM latest
+            WHERE attempt = 1
+            GROUP BY cable_type
+            ORDER BY cable_type
+        ").map_err(|e| e.to_string())?;
+
+        let rows = statement.query_map(params![site], |row| Ok(CableTotal {
+            cable_type: row.get(0)?,
+            cables: row.get(1)?,
+            total_length_m: row.get(2)?,
+        })).map_err(|e| e.to_string())?;
+
+        rows.collect::<Result<Vec<CableTotal>, rusqlite::Error>>().map_err(|e| e.to_string())
+    }
+}
+
+fn ingest_export(transaction: &Transaction, site: &str, path: &str, input: &SweepReport) -> Result<Ingested, rusqlite::Error> {
+
+    transaction.execute("
+        INSERT INTO exports (path, site, version, ingested) VALUES (?1, ?2, ?3, ?4)
+        ON CONFLICT (path) DO UPDATE SET site = excluded.site, version = excluded.version, ingested = excluded.ingested
+    ", params![path, site, input.version, Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()])?;
+
+    let export_id: i64 = transaction.query_row("SELECT id FROM exports WHERE path = ?1", params![path], |row| row.get(0))?;
+
+    for device in &input.devices {
+        transaction.execute("
+            INSERT INTO devices (serial_number, model, sw_version, calibration_date, signature) VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT (serial_number) DO UPDATE SET model = excluded.model, sw_version = excluded.sw_version,
+                calibration_date = excluded.calibration_date, signature = excluded.signature
+        ", params![device.serial_number, device.model, device.sw_version, format_time(&device.calibration_date), device.signature])?;
+    }
+
+    let mut ingested = Ingested { cables: input.reports.len(), ..Default::default() };
+
+    for report in &input.reports {
+
+        if let (Some(id), Some(state)) = (&report.dtf_state_id, &report.dtf_state) {
+            upsert_state(transaction, export_id, id, &state.test_type, Some(state.rx_khz), state.points, Some(state.limit_distance), state.vf, Some(state.cable_loss_dbm), &state.limit)?;
+        }
+        if let (Some(id), Some(state)) = (&report.rl_state_id, &report.rl_state) {
+            upsert_state(transaction, export_id, id, &state.test_type, state.rx_khz, state.points, None, None, None, &state.limit)?;
+        }
+
+        let tests = [
+            ("DTF", report.dtf_result.as_ref(), report.dtf_state_id.as_ref(), report.dtf_trace.as_ref(), report.dtf_marker),
+            ("RL", report.rl_result.as_ref(), report.rl_state_id.as_ref(), report.rl_trace.as_ref(), None),
+        ];
+
+        for (test_type, result, state_id, trace, marker) in tests {
+            let Some(result) = result else {
+                continue;
+            };
+
+            match upsert_test(transaction, export_id, site, report, test_type, result, state_id, trace, marker, device_for(input, result))? {
+                true => ingested.new += 1,
+                false => ingested.updated += 1,
+            }
+        }
+    }
+
+    Ok(ingested)
+}
+
+///The device that ran a test. A test is only put down to a device when there is no other it
+///could have been: it lists just the one, or lists none and the export has no other.
+fn device_for<'a>(input: &'a SweepReport, result: &TestResult) -> Option<&'a Device> {
+    match input.devices_for_test(result).as_slice() {
+        [device] => Some(device),
+        _ => None,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn upsert_state(transaction: &Transaction, export_id: i64, state_id: &str, test_type: &str, rx_khz: Option<(u64, u64)>, points: u64,
+    distance_m: Option<u64>, vf: Option<f64>, cable_loss: Option<f64>, limit: &Limit) -> Result<(), rusqlite::Error> {
+
+    transaction.execute("
+        INSERT OR REPLACE INTO states (export_id, state_id, test_type, rx_start_khz, rx_stop_khz, points, distance_m, vf, cable_loss_db_per_m, limit_unit, limit_value)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+    ", params![
+        export_id, state_id, test_type,
+        rx_khz.map(|e| e.0 as i64), rx_khz.map(|e| e.1 as i64),
+        points as i64, distance_m.map(|e| e as i64), vf, cable_loss,
+        limit.unit.to_string(), limit.value_in(limit.unit),
+    ])?;
+
+    Ok(())
+}
+
+///Returns whether the test is new to the database.
+#[allow(clippy::too_many_arguments)]
+fn upsert_test(transaction: &Transaction, export_id: i64, site: &str, report: &Report, test_type: &str, result: &TestResult,
+    state_id: Option<&String>, trace: Option<&Trace>, marker: Option<f64>, device: Option<&Device>) -> Result<bool, rusqlite::Error> {
+
+    let serial = device.map_or("", |device| device.serial_number.as_str());
+    let recorded = format_recorded(&result.time);
+
+    let exists = transaction.query_row("SELECT 1 FROM tests WHERE device_serial = ?1 AND test_id = ?2 AND recorded = ?3",
+        params![serial, result.id, recorded], |_| Ok(())).optional()?.is_some();
+
+    //DTF setups are labelled with the catalogue cable they match, otherwise by their VF and loss
+    let cable_type = report.dtf_state.as_ref().filter(|_| test_type == "DTF").map(|state| match state.vf {
+        Some(vf) => match cables::identify(vf, state.cable_loss_dbm) {
+            Some(cable) => cable.name.to_string(),
+            None => format!("VF {:.2}, {:.3} dB/m", vf, state.cable_loss_dbm),
+        },
+        None => "Unknown".to_string(),
+    });
+
+    transaction.execute("
+        INSERT OR REPLACE INTO tests (device_serial, test_id, recorded, time, export_id, state_id, site, tag, test_type, cable_type)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+    ", params![serial, result.id, recorded, format_time(&result.time), export_id, state_id, site, report.tag, test_type, cable_type])?;
+
+    transaction.execute("
+        INSERT OR REPLACE INTO results (device_serial, test_id, recorded, measurement_type, unit, max_x, max_y, min_x, min_y, average, ripple, pass, marker_m, calibrated_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+    ", params![
+        serial, result.id, recorded, result.measurement_type, result.unit.to_string(),
+        result.max.0, result.max.1, result.min.0, result.min.1, result.avg, result.ripple, result.pass,
+        marker, format_time(&result.calibrated_at()),
+    ])?;
+
+    match trace {
+        Some(trace) => transaction.execute("INSERT OR REPLACE INTO traces (device_serial, test_id, recorded, points) VALUES (?1, ?2, ?3, ?4)",
+            params![serial, result.id, recorded, serde_json::to_string(&trace.points).unwrap()])?,
+        None => transaction.execute("DELETE FROM traces WHERE device_serial = ?1 AND test_id = ?2 AND recorded = ?3", params![serial, result.id, recorded])?,
+    };
+
+    Ok(!exists)
+}
+
+///A question `query` can answer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Question {
+    Failures,
+    Retests,
+    Cables,
+}
+
+impl FromStr for Question {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.trim().to_ascii_lowercase().as_str() {
+            "failures" => Ok(Question::Failures),
+            "retests" => Ok(Question::Retests),
+            "cables" => Ok(Question::Cables),
+            _ => Err(format!("Unknown question {}, expected failures, retests or cables", input)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedTest {
+    pub site: String,
+    pub tag: String,
+    pub test_type: String,
+    pub time: String,
+    pub device_serial: String,
+    ///worst point, in `unit`
+    pub worst: f64,
+    pub unit: String,
+    pub passed_later: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Retest {
+    pub site: String,
+    pub tag: String,
+    pub test_type: String,
+    pub attempts: usize,
+    pub first: String,
+    pub last: String,
+    pub last_passed: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CableTotal {
+    pub cable_type: String,
+    pub cables: usize,
+    pub total_length_m: Option<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipa_report;
+
+    fn fixture(tz: Tz) -> SweepReport {
+        ipa_report::fixture_report(tz).0
+    }
+
+    #[test]
+    fn ingesting_again_under_another_timezone_updates_the_tests() {
+        let mut database = Database::open(Path::new(":memory:")).unwrap();
+
+        let first = database.ingest("site", "fixtures/v1.0", &fixture(Tz::UTC)).unwrap();
+        let second = database.ingest("site", "fixtures/v1.0", &fixture(Tz::Australia__Sydney)).unwrap();
+
+        assert_eq!((first.new, first.updated), (10, 0));
+        assert_eq!((second.new, second.updated), (0, 10));
+        assert!(database.retests(None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn a_test_listing_several_devices_is_put_down_to_none() {
+        let mut input = fixture(Tz::UTC);
+        let mut second = input.devices[0].clone();
+        second.ID = "2".to_string();
+        second.serial_number = "IE118501".to_string();
+        input.devices.push(second);
+
+        let both = input.reports[0].dtf_result.as_mut().unwrap();
+        both.device_ids = vec!["1".to_string(), "2".to_string()];
+        both.pass = false;
+        let one = input.reports[1].dtf_result.as_mut().unwrap();
+        one.device_ids = vec!["2".to_string()];
+        one.pass = false;
+
+        let mut database = Database::open(Path::new(":memory:")).unwrap();
+        database.ingest("site", "fixtures/v1.0", &input).unwrap();
+
+        let serials: Vec<(String, String)> = database.failures(None).unwrap().into_iter().map(|e| (e.tag, e.device_serial)).collect();
+        assert_eq!(serials, [("F-L29-226".to_string(), String::new()), ("F-L29-227".to_string(), "IE118501".to_string())]);
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::BTreeMap;
+
+use serde_derive::Serialize;
+
+use crate::{compare::{self, TraceDelta}, ipa_report::{Report, SweepReport}, sort};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Added,
+    Removed,
+    Changed,
+    Unchanged,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trend {
+    Improved,
+    Regressed,
+    ///moved further than the tolerance, neither better nor worse (length)
+    Changed,
+    Unchanged,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transition {
+    ///failed in the old export, passes in the new one
+    Fixed,
+    ///passed in the old export, fails in the new one
+    Broke,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricChange {
+    pub old: Option<f64>,
+    pub new: Option<f64>,
+    ///`new - old`
+    pub delta: Option<f64>,
+    pub trend: Trend,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TagDiff {
+    pub tag: String,
+    pub change: Change,
+    pub length: MetricChange,
+    pub vswr: MetricChange,
+    pub rl: MetricChange,
+    pub pass: (Option<bool>, Option<bool>),
+    pub transition: Option<Transition>,
+    pub dtf_trace: Option<TraceDelta>,
+    pub rl_trace: Option<TraceDelta>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SweepDiff {
+    pub tags: Vec<TagDiff>,
+}
+
+impl SweepDiff {
+    pub fn count(&self, change: Change) -> usize {
+        self.tags.iter().filter(|e| e.change == change).count()
+    }
+}
+
+///Differences smaller than these are reported as unchanged.
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerances {
+    pub length: f64,
+    pub vswr: f64,
+    pub rl: f64,
+}
+
+impl Default for Tolerances {
+    fn default() -> Self {
+        Tolerances { length: 0.5, vswr: 0.05, rl: 0.5 }
+    }
+}
+
+///Matches the cables of two exports of the same site by tag. When a tag was tested more than once
+///in an export the last attempt is used, as that is the one that was handed over.
+pub fn diff(old: &SweepReport, new: &SweepReport, tolerances: Tolerances) -> SweepDiff {
+
+    let old_tags: BTreeMap<&str, &Report> = old.reports.iter().map(|e| (e.tag.as_str(), e)).collect();
+    let new_tags: BTreeMap<&str, &Report> = new.reports.iter().map(|e| (e.tag.as_str(), e)).collect();
+
+    let mut tags: Vec<&str> = old_tags.keys().chain(new_tags.keys()).copied().collect();
+    tags.sort_by(|a, b| sort::natural_cmp(a, b));
+    tags.dedup();
+
+    let tags = tags.into_iter().map(|tag| {
+
+        let (old, new) = (old_tags.get(tag).copied(), new_tags.get(tag).copied());
+
+        //lower VSWR is better, RL is negative so lower (further from 0) is better too
+        let (length, vswr, rl, dtf_trace, rl_trace) = match (old, new) {
+            (Some(old), Some(new)) => {
+                let comparison = compare::compare(old, new);
+                (
+                    metric(comparison.length, tolerances.length, false),
+                    metric(comparison.vswr, tolerances.vswr, true),
+                    metric(comparison.rl, tolerances.rl, true),
+                    comparison.dtf_trace,
+                    comparison.rl_trace,
+                )
+            },
+            _ => (
+                metric((old.and_then(|e| e.dtf_marker), new.and_then(|e| e.dtf_marker)), tolerances.length, false),
+                metric((old.and_then(worst_vswr), new.and_then(worst_vswr)), tolerances.vswr, true),
+                metric((old.and_then(worst_rl), new.and_then(worst_rl)), tolerances.rl, true),
+                None,
+                None,
+            ),
+        };
+
+        let pass = (old.and_then(Report::passed), new.and_then(Report::passed));
+        let transition = match pass {
+            (Some(false), Some(true)) => Some(Transition::Fixed),
+            (Some(true), Some(false)) => Some(Transition::Broke),
+            _ => None,
+        };
+
+        let change = match (old, new) {
+            (None, _) => Change::Added,
+            (_, None) => Change::Removed,
+            _ if transition.is_some() || [&length, &vswr, &rl].iter().any(|e| e.trend != Trend::Unchanged) => Change::Changed,
+            _ => Change::Unchanged,
+        };
+
+        TagDiff { tag: tag.to_string(), change, length, vswr, rl, pass, transition, dtf_trace, rl_trace }
+    }).collect();
+
+    SweepDiff { tags }
+}
+
+fn worst_vswr(report: &Report) -> Option<f64> {
+    report.dtf_result.as_ref().map(|e| e.max.1)
+}
+
+fn worst_rl(report: &Report) -> Option<f64> {
+    report.rl_result.as_ref().map(|e| e.max.1)
+}
+
+fn metric(values: (Option<f64>, Option<f64>), tolerance: f64, lower_is_better: bool) -> MetricChange {
+
+    let delta = match values {
+        (Some(old), Some(new)) => Some(new - old),
+        _ => None,
+    };
+
+    let trend = match delta {
+        Some(delta) if delta.abs() <= tolerance => Trend::Unchanged,
+        Some(_) if !lower_is_better => Trend::Changed,
+        Some(delta) if delta < 0.0 => Trend::Improved,
+        Some(_) => Trend::Regressed,
+        None => Trend::Unchanged,
+    };
+
+    MetricChange { old: values.0, new: values.1, delta, trend }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Tz;
+
+    use super::*;
+    use crate::ipa_report::fixture_report;
+
+    fn fixture() -> SweepReport {
+        fixture_report(Tz::UTC).0
+    }
+
+    fn tag<'a>(diff: &'a SweepDiff, tag: &str) -> &'a TagDiff {
+        diff.tags.iter().find(|e| e.tag == tag).unwrap()
+    }
+
+    #[test]
+    fn matches_cables_by_tag_whatever_the_order() {
+        let old = fixture();
+        let mut new = fixture();
+        new.reports.reverse();
+        new.reports.retain(|e| e.tag != "F-L29-227");
+        new.reports[0].tag = "F-L29-300".to_string();
+
+        let diff = diff(&old, &new, Tolerances::default());
+
+        let tags: Vec<&str> = diff.tags.iter().map(|e| e.tag.as_str()).collect();
+        assert_eq!(tags, ["F-L29-9", "F-L29-10", "F-L29-226", "F-L29-227", "F-L29-228", "F-L29-300"]);
+        assert_eq!(tag(&diff, "F-L29-10").change, Change::Removed);
+        assert_eq!(tag(&diff, "F-L29-227").change, Change::Removed);
+        assert_eq!(tag(&diff, "F-L29-300").change, Change::Added);
+        assert_eq!(diff.count(Change::Unchanged), 3);
+    }
+
+    #[test]
+    fn changes_inside_the_tolerances_are_unchanged() {
+        let old = fixture();
+        let mut new = fixture();
+        new.reports[0].dtf_result.as_mut().unwrap().max.1 += 0.04;
+        new.reports[0].rl_result.as_mut().unwrap().max.1 -= 0.4;
+        new.reports[1].dtf_result.as_mut().unwrap().max.1 += 0.1;
+        new.reports[1].rl_result.as_mut().unwrap().max.1 -= 1.0;
+        new.reports[2].dtf_marker = new.reports[2].dtf_marker.map(|e| e + 1.0);
+
+        let diff = diff(&old, &new, Tolerances::default());
+
+        let cable = tag(&diff, "F-L29-226");
+        assert_eq!(cable.change, Change::Unchanged);
+        assert_eq!((cable.vswr.trend, cable.rl.trend), (Trend::Unchanged, Trend::Unchanged));
+
+        let cable = tag(&diff, "F-L29-227");
+        assert_eq!(cable.change, Change::Changed);
+        assert_eq!((cable.vswr.trend, cable.rl.trend), (Trend::Regressed, Trend::Improved));
+
+        assert_eq!(tag(&diff, "F-L29-228").length.trend, Trend::Changed);
+
+        let loose = Tolerances { length: 2.0, vswr: 0.2, rl: 2.0 };
+        assert_eq!(super::diff(&old, &new, loose).count(Change::Unchanged), 5);
+    }
+
+    #[test]
+    fn reports_pass_fail_transitions() {
+        let mut old = fixture();
+        let mut new = fixture();
+        old.reports[0].rl_result.as_mut().unwrap().pass = false;
+        new.reports[1].dtf_result.as_mut().unwrap().pass = false;
+        //failing in both is not a transition
+        old.reports[2].dtf_result.as_mut().unwrap().pass = false;
+        new.reports[2].rl_result.as_mut().unwrap().pass = false;
+
+        let diff = diff(&old, &new, Tolerances::default());
+
+        assert_eq!(tag(&diff, "F-L29-226").transition, Some(Transition::Fixed));
+        assert_eq!(tag(&diff, "F-L29-227").transition, Some(Transition::Broke));
+        assert_eq!(tag(&diff, "F-L29-227").change, Change::Changed);
+        assert_eq!(tag(&diff, "F-L29-228").pass, (Some(false), Some(false)));
+        assert_eq!(tag(&diff, "F-L29-228").transition, None);
+    }
+
+    #[test]
+    fn the_last_attempt_at_a_tag_is_used() {
+        let old = fixture();
+        let mut new = fixture();
+        let mut retest = new.reports[0].clone();
+        retest.rl_result.as_mut().unwrap().pass = false;
+        new.reports.push(retest);
+
+        let diff = diff(&old, &new, Tolerances::default());
+
+        assert_eq!(diff.tags.len(), 5);
+        assert_eq!(tag(&diff, "F-L29-226").transition, Some(Transition::Broke));
+    }
+}
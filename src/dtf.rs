@@ -0,0 +1,262 @@
+use crate::{ipa_report::Report, metrics, trace::Trace};
+
+#[derive(Debug, Clone)]
+pub struct EventSettings {
+    ///smallest VSWR peak reported as an event
+    pub threshold: f64,
+    ///peaks at or above this along the line are reported as possible damage
+    pub damage_threshold: f64,
+    ///peaks closer than this to the tester are the launch jumper/connector
+    pub launch_distance: f64,
+    ///peaks this close to the cable end are the end of line
+    pub end_tolerance: f64,
+    ///peaks closer together than this are treated as one reflection
+    pub min_separation: f64,
+}
+
+impl Default for EventSettings {
+    fn default() -> Self {
+        EventSettings { 
+            threshold: 1.05, 
+            damage_threshold: 1.5, 
+            launch_distance: 1.0, 
+            end_tolerance: 0.5, 
+            min_separation: 0.3 
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventKind {
+    ///tester port, test jumper or the first connector
+    Launch,
+    Connector,
+    PossibleDamage,
+    ///antenna, load or open at the end of the cable
+    EndOfLine,
+    BeyondEnd,
+}
+
+impl EventKind {
+    pub fn description(&self) -> &'static str {
+        match self {
+            EventKind::Launch => "Launch connector / test jumper",
+            EventKind::Connector => "Connector / jumper",
+            EventKind::PossibleDamage => "Possible damage",
+            EventKind::EndOfLine => "End of line (antenna/load)",
+            EventKind::BeyondEnd => "Beyond cable end",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub distance: f64,
+    pub vswr: f64,
+    ///return loss equivalent of the VSWR, negative dB like the RL results
+    pub return_loss: f64,
+    pub kind: EventKind,
+}
+
+///Rescales a DTF result for a different velocity factor, and optionally re-applies cable loss compensation,
+///for when the wrong cable type was selected on the tester.
+///
+///Distances scale by `vf / recorded vf`. The tester adds 2 x loss x distance to every reflection, so that
+///compensation is swapped for one at the new loss and distance, and the worst point is taken again from the
+///trace between P1 and P2.
+pub fn recompute(report: &mut Report, vf: f64, loss_db_per_m: Option<f64>) -> Result<(), String> {
+
+    if !vf.is_finite() || vf <= 0.0 {
+        return Err(format!("Invalid velocity factor {}", vf));
+    }
+    if loss_db_per_m.is_some_and(|loss| !loss.is_finite() || loss < 0.0) {
+        return Err(format!("Invalid cable loss {} dB/m", loss_db_per_m.unwrap_or_default()));
+    }
+
+    let state = report.dtf_state.as_mut().ok_or(format!("No DTF test on {}", report.tag))?;
+    let old_vf = state.vf.filter(|vf| vf.is_finite() && *vf > 0.0).ok_or(format!("No valid VF recorded for {}", report.tag))?;
+    let old_loss = state.cable_loss_dbm;
+    let new_loss = loss_db_per_m.unwrap_or(old_loss);
+    let scale = vf / old_vf;
+
+    state.vf = Some(vf);
+    state.cable_loss_dbm = new_loss;
+    state.limit_distance = (state.limit_distance as f64 * scale).round() as u64;
+
+    if let Some(marker) = report.dtf_marker.as_mut() {
+        *marker *= scale;
+    }
+
+    if let Some(trace) = report.dtf_trace.as_mut() {
+        for point in trace.points.iter_mut() {
+            let distance = point.0 * scale;
+            let compensation = 2.0 * (new_loss * distance - old_loss * point.0);
+            let reflection = metrics::vswr_to_return_loss(point.1) + compensation;
+            point.1 = metrics::reflection_to_vswr(10f64.powf(reflection / 20.0));
+            point.0 = distance;
+        }
+    }
+
+    if let Some(result) = report.dtf_result.as_mut() {
+        result.max.0 *= scale;
+        result.min.0 *= scale;
+
+        if let Some(points) = report.dtf_trace.as_ref().map(|trace| &trace.points) {
+            let range = &points[(result.p1 as usize).min(points.len())..(result.p2 as usize + 1).min(points.len())];
+            if let Some(max) = range.iter().cloned().reduce(|a, b| if b.1 > a.1 { b } else { a }) {
+                result.max = max;
+            }
+            if let Some(min) = range.iter().cloned().reduce(|a, b| if b.1 < a.1 { b } else { a }) {
+                result.min = min;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+///Local maxima at or above `threshold` as (distance, VSWR), merging any closer together than `min_separation`.
+pub fn find_peaks(trace: &Trace, threshold: f64, min_separation: f64) -> Vec<(f64, f64)> {
+
+    let points = &trace.points;
+    let mut peaks: Vec<(f64, f64)> = Vec::new();
+
+    for i in 0..points.len() {
+        let (distance, vswr) = points[i];
+        let rising = i == 0 || vswr >= points[i - 1].1;
+        let falling = i + 1 == points.len() || vswr > points[i + 1].1;
+
+        if vswr < threshold || !rising || !falling {
+            continue;
+        }
+
+        match peaks.last_mut() {
+            Some(last) if distance - last.0 < min_separation => {
+                if vswr > last.1 {
+                    *last = (distance, vswr);
+                }
+            },
+            _ => peaks.push((distance, vswr)),
+        }
+    }
+
+    peaks
+}
+
+///Estimates where the cable ends from the trace alone: the last peak reflecting at least half as much as the largest one.
+///
+///Smaller peaks past that point are ringing or whatever is connected beyond the antenna, not the cable.
+pub fn estimate_cable_end(trace: &Trace, settings: &EventSettings) -> Option<f64> {
+
+    let peaks = find_peaks(trace, settings.threshold, settings.min_separation);
+    let largest = peaks.iter().map(|peak| peak.1).reduce(f64::max)?;
+
+    peaks.iter()
+        .filter(|peak| peak.1 - 1.0 >= (largest - 1.0) / 2.0)
+        .map(|peak| peak.0)
+        .reduce(f64::max)
+}
+
+///Finds every reflection peak above the threshold in a DTF trace, classified by its position along a cable ending at `cable_end`.
+pub fn find_events(trace: &Trace, cable_end: Option<f64>, settings: &EventSettings) -> Vec<Event> {
+
+    let peaks = find_peaks(trace, settings.threshold, settings.min_separation);
+
+    peaks.into_iter().map(|(distance, vswr)| {
+
+        let kind = match cable_end {
+            Some(end) if (distance - end).abs() <= settings.end_tolerance => EventKind::EndOfLine,
+            Some(end) if distance > end => EventKind::BeyondEnd,
+            _ if distance <= settings.launch_distance => EventKind::Launch,
+            _ if vswr >= settings.damage_threshold => EventKind::PossibleDamage,
+            _ => EventKind::Connector,
+        };
+
+        Event { distance, vswr, return_loss: metrics::vswr_to_return_loss(vswr), kind }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Tz;
+
+    use super::*;
+    use crate::ipa_report;
+
+    fn cable() -> Report {
+        ipa_report::fixture_report(Tz::UTC).0.reports.remove(0)
+    }
+
+    #[test]
+    fn a_vf_change_moves_the_loss_compensation_with_the_distance() {
+        let original = cable();
+        let mut report = cable();
+
+        recompute(&mut report, 0.44, None).unwrap();
+
+        let state = report.dtf_state.as_ref().unwrap();
+        assert_eq!(state.limit_distance, 20);
+        assert!((report.dtf_marker.unwrap() - 26.89 / 2.0).abs() < 1e-9);
+
+        //half the distance takes off half of the 2 x loss x distance the tester added
+        let (distance, vswr) = original.dtf_trace.as_ref().unwrap().points[200];
+        let point = report.dtf_trace.as_ref().unwrap().points[200];
+        let expected = metrics::vswr_to_return_loss(vswr) - 0.0656 * distance;
+        assert!((point.0 - distance / 2.0).abs() < 1e-9);
+        assert!((metrics::vswr_to_return_loss(point.1) - expected).abs() < 1e-9);
+    }
+
+    ///A flat 30 m trace sampled every 0.1 m, with a 0.4 m wide spike of the given VSWR at each distance.
+    fn trace(peaks: &[(f64, f64)]) -> Trace {
+        Trace { points: (0..=300).map(|i| {
+            let distance = i as f64 / 10.0;
+            let vswr = peaks.iter()
+                .map(|(at, vswr)| 1.0 + (vswr - 1.0) * (1.0 - (distance - at).abs() / 0.2).max(0.0))
+                .fold(1.0, f64::max);
+            (distance, vswr)
+        }).collect() }
+    }
+
+    fn distances(peaks: &[(f64, f64)]) -> Vec<f64> {
+        peaks.iter().map(|peak| (peak.0 * 10.0).round() / 10.0).collect()
+    }
+
+    #[test]
+    fn finds_peaks_over_the_threshold_and_merges_close_ones() {
+        let peaks = find_peaks(&trace(&[(0.5, 1.3), (10.0, 1.2), (10.2, 1.25), (20.0, 1.03)]), 1.05, 0.3);
+
+        assert_eq!(distances(&peaks), [0.5, 10.2]);
+        assert_eq!(peaks[1].1, 1.25);
+        assert!(find_peaks(&trace(&[]), 1.05, 0.3).is_empty());
+    }
+
+    #[test]
+    fn the_cable_ends_at_the_last_peak_at_least_half_the_largest() {
+        let settings = EventSettings::default();
+
+        //ringing past the antenna reflects less than half as much
+        assert_eq!(estimate_cable_end(&trace(&[(0.5, 1.3), (12.0, 1.8), (20.0, 1.3)]), &settings), Some(12.0));
+        assert_eq!(estimate_cable_end(&trace(&[(12.0, 1.8), (20.0, 1.5)]), &settings), Some(20.0));
+        assert_eq!(estimate_cable_end(&trace(&[]), &settings), None);
+    }
+
+    #[test]
+    fn classifies_events_by_position_and_size() {
+        let trace = trace(&[(0.5, 1.2), (5.0, 1.1), (8.0, 1.6), (12.3, 2.0), (20.0, 1.2)]);
+        let events = find_events(&trace, Some(12.0), &EventSettings::default());
+        let kinds: Vec<EventKind> = events.iter().map(|event| event.kind.clone()).collect();
+
+        assert_eq!(kinds, [EventKind::Launch, EventKind::Connector, EventKind::PossibleDamage, EventKind::EndOfLine, EventKind::BeyondEnd]);
+
+        //without a cable end nothing is put down to the end of the line
+        let kinds: Vec<EventKind> = find_events(&trace, None, &EventSettings::default()).into_iter().map(|event| event.kind).collect();
+        assert_eq!(kinds, [EventKind::Launch, EventKind::Connector, EventKind::PossibleDamage, EventKind::PossibleDamage, EventKind::Connector]);
+    }
+
+    #[test]
+    fn rejects_invalid_velocity_factors() {
+        for vf in [0.0, -0.8, f64::NAN, f64::INFINITY] {
+            assert!(recompute(&mut cable(), vf, None).is_err());
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::process;
+
+use owo_colors::OwoColorize;
+
+use crate::color;
+
+///Process exit codes, documented in the README.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitCode {
+    Ok = 0,
+    ///bad arguments, the same code argh exits with
+    Usage = 1,
+    ///the export couldn't be read or Report.xml couldn't be parsed
+    ParseError = 2,
+    ///Report.xml references a CSV that isn't in the export
+    AssetMissing = 3,
+    ///warnings were produced and --strict was given
+    Warnings = 4,
+    ///a test failed its limit
+    TestFailed = 5,
+    ///scheduled cables are missing or at the wrong length
+    Incomplete = 6,
+    ///an output couldn't be written: export files, charts, manifest, index, progress report or database
+    WriteFailed = 7,
+    ///a check the command was asked for failed: signatures, manifest, --fail-on-expired-cal
+    CheckFailed = 8,
+}
+
+///Why an export couldn't be loaded.
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub code: ExitCode,
+    pub message: String,
+}
+
+impl Failure {
+    pub fn usage(message: String) -> Failure {
+        Failure { code: ExitCode::Usage, message }
+    }
+
+    pub fn parse(message: String) -> Failure {
+        Failure { code: ExitCode::ParseError, message }
+    }
+
+    pub fn asset_missing(message: String) -> Failure {
+        Failure { code: ExitCode::AssetMissing, message }
+    }
+
+    pub fn write(message: String) -> Failure {
+        Failure { code: ExitCode::WriteFailed, message }
+    }
+}
+
+pub fn fail(failure: Failure) -> ! {
+    let label = match color::stderr_enabled() {
+        true => "ERROR:".red().bold().to_string(),
+        false => "ERROR:".to_string(),
+    };
+    eprintln!("{} {}", label, failure.message);
+    process::exit(failure.code as i32)
+}
+
+///Exits with the most serious of `codes`, or returns when they are all `Ok`. A failed test outranks an
+///incomplete schedule, which outranks warnings and then the opt-in checks.
+pub fn exit_with(codes: &[ExitCode]) {
+    let code = codes.iter().max_by_key(|code| severity(**code)).copied().unwrap_or(ExitCode::Ok);
+    if code != ExitCode::Ok {
+        process::exit(code as i32);
+    }
+}
+
+fn severity(code: ExitCode) -> u8 {
+    match code {
+        ExitCode::Ok => 0,
+        ExitCode::CheckFailed => 1,
+        ExitCode::Warnings => 2,
+        ExitCode::Incomplete => 3,
+        ExitCode::TestFailed => 4,
+        ExitCode::AssetMissing => 5,
+        ExitCode::ParseError => 6,
+        ExitCode::WriteFailed => 7,
+        ExitCode::Usage => 8,
+    }
+}
@@ -0,0 +1,179 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use serde_derive::Serialize;
+
+use crate::ipa_report::{SweepReport, TestResult};
+
+///One cable on a test certificate.
+#[derive(Debug, Serialize)]
+pub struct CertificateRow {
+    pub tag: String,
+    pub length_m: Option<f64>,
+    pub vswr: Option<f64>,
+    pub vswr_at_m: Option<f64>,
+    pub dtf_pass: Option<bool>,
+    pub dtf_time: Option<String>,
+    pub rl_db: Option<f64>,
+    pub rl_pass: Option<bool>,
+    pub rl_time: Option<String>,
+    ///"Valid" when every device that ran this cable's tests was inside its factory calibration interval
+    pub device_calibration: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CertificateDevice {
+    pub model: String,
+    pub serial_number: String,
+    pub sw_version: String,
+    pub calibration_date: String,
+    pub calibration_due: String,
+    ///"Valid" or "Expired", at the time of the latest test the device ran
+    pub calibration_status: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Certificate {
+    pub version: String,
+    pub devices: Vec<CertificateDevice>,
+    pub cables: Vec<CertificateRow>,
+}
+
+impl Certificate {
+
+    pub fn from_sweep_report(input: &SweepReport, cal_interval_months: u32) -> Certificate {
+
+        let devices = input.devices.iter().map(|device| {
+
+            let latest_test = input.reports.iter()
+                .flat_map(|report| report.results())
+                .filter(|(_, result)| input.devices_for_test(result).iter().any(|e| e.ID == device.ID))
+                .map(|(_, result)| result.time)
+                .max();
+
+            CertificateDevice { 
+                model: device.model.clone(), 
+                serial_number: device.serial_number.clone(), 
+                sw_version: device.sw_version.clone(), 
+                calibration_date: device.calibration_date.date_naive().to_string(), 
+                calibration_due: device.calibration_due(cal_interval_months).date_naive().to_string(), 
+                calibration_status: calibration_status(latest_test.is_none_or(|time| device.calibration_valid_at(&time, cal_interval_months))),
+            }
+        }).collect();
+
+        let cables = input.reports.iter().map(|report| {
+
+            let valid = report.results().iter().all(|(_, result)| {
+                input.devices_for_test(result).iter().all(|device| device.calibration_valid_at(&result.time, cal_interval_months))
+            });
+
+            CertificateRow { 
+                tag: report.tag.clone(), 
+                length_m: report.dtf_marker, 
+                vswr: report.dtf_result.as_ref().map(|e| e.max.1), 
+                vswr_at_m: report.dtf_result.as_ref().map(|e| e.max.0), 
+                dtf_pass: report.dtf_result.as_ref().map(|e| e.pass), 
+                dtf_time: report.dtf_result.as_ref().map(format_time), 
+                rl_db: report.rl_result.as_ref().map(|e| e.max.1), 
+                rl_pass: report.rl_result.as_ref().map(|e| e.pass), 
+                rl_time: report.rl_result.as_ref().map(format_time), 
+                device_calibration: calibration_status(valid),
+            }
+        }).collect();
+
+        Certificate { version: input.version.clone(), devices, cables }
+    }
+}
+
+fn calibration_status(valid: bool) -> String {
+    match valid {
+        true => "Valid".to_string(),
+        false => "Expired".to_string(),
+    }
+}
+
+fn format_time(result: &TestResult) -> String {
+    result.time.format("%Y-%m-%d %H:%M %Z").to_string()
+}
+
+///Writes report.json, report.csv and certificate.html into `dir`, returning the paths written.
+pub fn write_exports(certificate: &Certificate, dir: &Path) -> Result<Vec<PathBuf>, String> {
+
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+    let json_path = dir.join("report.json");
+    let json = serde_json::to_string_pretty(certificate).map_err(|e| e.to_string())?;
+    fs::write(&json_path, json).map_err(|e| e.to_string())?;
+
+    let csv_path = dir.join("report.csv");
+    let mut writer = csv::Writer::from_path(&csv_path).map_err(|e| e.to_string())?;
+    for row in &certificate.cables {
+        writer.serialize(row).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+
+    let html_path = dir.join("certificate.html");
+    fs::write(&html_path, certificate_html(certificate)).map_err(|e| e.to_string())?;
+
+    Ok(vec![json_path, csv_path, html_path])
+}
+
+fn certificate_html(certificate: &Certificate) -> String {
+
+    let mut html = String::new();
+
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Cable Sweep Certificate</title>\n");
+    html.push_str("<style>body{font-family:sans-serif}table{border-collapse:collapse}td,th{border:1px solid #999;padding:2px 8px}.fail{color:#b00}</style>\n");
+    html.push_str("</head>\n<body>\n<h1>Cable Sweep Certificate</h1>\n");
+
+    html.push_str("<h2>Test Equipment</h2>\n<table>\n<tr><th>Model</th><th>Serial</th><th>Version</th><th>Calibrated</th><th>Due</th><th>Status</th></tr>\n");
+    for device in &certificate.devices {
+        html.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td{}>{}</td></tr>\n",
+            escape(&device.model),
+            escape(&device.serial_number),
+            escape(&device.sw_version),
+            device.calibration_date,
+            device.calibration_due,
+            fail_class(device.calibration_status == "Valid"),
+            device.calibration_status,
+        ));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Cables</h2>\n<table>\n<tr><th>Tag</th><th>Length (m)</th><th>VSWR</th><th>at (m)</th><th>RL (dB)</th><th>Pass</th><th>Tested</th><th>Equipment Cal</th></tr>\n");
+    for row in &certificate.cables {
+        let pass = row.dtf_pass.unwrap_or(true) && row.rl_pass.unwrap_or(true);
+        html.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td{}>{}</td><td>{}</td><td{}>{}</td></tr>\n",
+            escape(&row.tag),
+            format_value(row.length_m),
+            format_value(row.vswr),
+            format_value(row.vswr_at_m),
+            format_value(row.rl_db),
+            fail_class(pass),
+            match pass { true => "Pass", false => "Fail" },
+            row.dtf_time.clone().or(row.rl_time.clone()).unwrap_or("N/A".to_string()),
+            fail_class(row.device_calibration == "Valid"),
+            row.device_calibration,
+        ));
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+
+    html
+}
+
+pub fn format_value(value: Option<f64>) -> String {
+    match value {
+        Some(e) => format!("{:.2}", e),
+        None => "N/A".to_string(),
+    }
+}
+
+fn fail_class(pass: bool) -> &'static str {
+    match pass {
+        true => "",
+        false => " class=\"fail\"",
+    }
+}
+
+pub fn escape(input: &str) -> String {
+    input.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
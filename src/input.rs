@@ -0,0 +1,133 @@
+use std::{collections::HashMap, fs, io::Read, path::{Component, Path, PathBuf}};
+
+pub const REPORT_NAME: &str = "Report.xml";
+
+///Where `name`, a path relative to an export or package as written in Report.xml or a manifest, lives
+///under `dir`. Names that are absolute or step outside with `..` are refused.
+pub fn relative_path(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(name);
+    let safe = !name.is_empty() && relative.components().all(|component| matches!(component, Component::Normal(_)));
+
+    match safe {
+        true => Ok(dir.join(relative)),
+        false => Err(format!("Refusing to use {} as a path inside the export", name)),
+    }
+}
+
+enum Source {
+    ///directory holding Report.xml and its assets
+    Directory(PathBuf),
+    ///every file in the archive, keyed by path relative to Report.xml
+    Zip(HashMap<String, Vec<u8>>),
+}
+
+///An export from the tester: a Report.xml (or the directory holding it) or the zip the tester produces.
+pub struct Input {
+    pub report_data: Vec<u8>,
+    source: Source,
+}
+
+impl Input {
+
+    pub fn open(path: &str) -> Result<Input, String> {
+
+        let path = PathBuf::from(path);
+
+        if path.is_dir() {
+            return Input::open_file(path.join(REPORT_NAME));
+        }
+
+        match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()) {
+            Some(e) if e == "zip" => Input::open_zip(path),
+            _ => Input::open_file(path),
+        }
+    }
+
+    fn open_file(path: PathBuf) -> Result<Input, String> {
+        let report_data = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let base_path = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+
+        Ok(Input { report_data, source: Source::Directory(base_path) })
+    }
+
+    fn open_zip(path: PathBuf) -> Result<Input, String> {
+
+        let file = fs::File::open(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut archive = zip::ZipArchive::new(file).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut files: HashMap<String, Vec<u8>> = HashMap::new();
+
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).map_err(|e| e.to_string())?;
+            if entry.is_dir() {
+                continue;
+            }
+            let mut data: Vec<u8> = Vec::new();
+            entry.read_to_end(&mut data).map_err(|e| e.to_string())?;
+            files.insert(entry.name().replace('\\', "/"), data);
+        }
+
+        //the tester sometimes nests the export in a folder inside the archive
+        let report_name = files.keys()
+            .filter(|name| name.rsplit('/').next() == Some(REPORT_NAME))
+            .min_by_key(|name| name.len())
+            .cloned()
+            .ok_or(format!("{}: no {} in archive", path.display(), REPORT_NAME))?;
+        let prefix = report_name.trim_end_matches(REPORT_NAME).to_string();
+
+        let files: HashMap<String, Vec<u8>> = files.into_iter()
+            .filter_map(|(name, data)| Some((name.strip_prefix(&prefix)?.to_string(), data)))
+            .collect();
+        let report_data = files.get(REPORT_NAME).cloned().unwrap();
+
+        Ok(Input { report_data, source: Source::Zip(files) })
+    }
+
+    ///Report.xml as text, with any invalid UTF-8 replaced.
+    pub fn report_xml(&self) -> String {
+        String::from_utf8_lossy(&self.report_data).to_string()
+    }
+
+    ///Reads an asset referenced by Report.xml. Names reaching outside the export are refused.
+    pub fn read_asset(&self, name: &str) -> Result<Vec<u8>, String> {
+        match &self.source {
+            Source::Directory(base_path) => fs::read(relative_path(base_path, name)?).map_err(|e| format!("{}: {}", name, e)),
+            Source::Zip(files) => {
+                relative_path(Path::new(""), name)?;
+                files.get(name).cloned().ok_or(format!("{}: not in archive", name))
+            },
+        }
+    }
+
+    pub fn has_asset(&self, name: &str) -> bool {
+        match &self.source {
+            Source::Directory(base_path) => relative_path(base_path, name).is_ok_and(|path| path.is_file()),
+            Source::Zip(files) => relative_path(Path::new(""), name).is_ok() && files.contains_key(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_names_outside_the_export() {
+        let dir = Path::new("package");
+
+        assert_eq!(relative_path(dir, "Test_001_DTF.csv"), Ok(dir.join("Test_001_DTF.csv")));
+        assert_eq!(relative_path(dir, "plots/F-L29-9_DTF.svg"), Ok(dir.join("plots/F-L29-9_DTF.svg")));
+        assert!(relative_path(dir, "../../x").is_err());
+        assert!(relative_path(dir, "plots/../../x").is_err());
+        assert!(relative_path(dir, "/etc/x").is_err());
+        assert!(relative_path(dir, "").is_err());
+    }
+
+    #[test]
+    fn assets_outside_the_export_are_not_read() {
+        let source = Input::open("fixtures/v1.0").unwrap();
+
+        assert!(source.has_asset("Test_001_DTF.csv"));
+        assert!(!source.has_asset("../v1.0/Test_001_DTF.csv"));
+        assert!(source.read_asset("../v1.0/Test_001_DTF.csv").unwrap_err().starts_with("Refusing"));
+    }
+}
@@ -1,23 +1,60 @@
-#![allow(non_snake_case)]
 
-use std::{fs, env};
+use crate::{input::Input, metrics::{self, Unit}, parallel, raw_ipa_report, trace::Trace};
+use chrono::{DateTime, Duration, Months, NaiveTime, Offset, TimeZone};
+use chrono_tz::Tz;
+use serde_derive::Serialize;
+use std::str::FromStr;
 
-use crate::raw_ipa_report;
-use chrono::{DateTime, FixedOffset, prelude::*};
+fn read_csv_marker_position(raw_csv: &str) -> Result<f64, String> {
 
-fn read_csv_marker_position(path: String) -> f64 {
+    let marker_line = raw_csv.lines().nth(13).ok_or("CSV is too short to hold the marker line")?;
+    let position = marker_line.split(',').nth(2).ok_or(format!("No marker position in: {}", marker_line))?;
 
-    let args: Vec<String> = env::args().collect();
-    let env_path = args.get(1).unwrap().trim_end_matches("Report.xml").to_string();
+    position.trim().parse().map_err(|_| format!("Invalid marker position: {}", position))
+}
 
-    let raw_csv = fs::read_to_string(env_path+&path).unwrap();
-    let lines_vec: Vec<&str> = raw_csv.lines().collect();
+///What is read from a test's CSV.
+struct CsvData {
+    trace: Trace,
+    ///only DTF traces have a marker
+    marker: Option<f64>,
+}
 
-    let marker_data: Vec<&str> = lines_vec.get(13).unwrap().split(',').collect();
-    
-    return marker_data.get(2).unwrap().to_owned().to_owned().parse().unwrap();
+impl CsvData {
+    fn from_raw(raw_csv: &str, is_dtf: bool) -> Result<CsvData, String> {
+        let marker = match is_dtf {
+            true => Some(read_csv_marker_position(raw_csv)?),
+            false => None,
+        };
+        Ok(CsvData { trace: Trace::from_csv(raw_csv)?, marker })
+    }
+}
+
+///Parses a numeric or boolean field of Report.xml, naming it in the error.
+fn parse_field<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value.trim().parse().map_err(|_| format!("Invalid {}: {}", name, value))
+}
+
+//the instrument records wall clock time without an offset
+fn localise(tz: Tz, time: chrono::NaiveDateTime, warnings: &mut Vec<Warning>) -> DateTime<Tz> {
+
+    if let Some(localised) = tz.from_local_datetime(&time).earliest() {
+        return localised;
+    }
+
+    //the clocks went forward over it, so the instrument was still on the offset from before the change
+    let before = tz.offset_from_utc_datetime(&(time - Duration::days(1))).fix();
+    let localised = tz.from_utc_datetime(&(time - before));
+
+    warnings.push(Warning { 
+        message: format!("Time {} does not exist in {}, read with the offset from before the clocks changed", time, tz), 
+        expected: "A time that exists in the site timezone".to_string(), 
+        result: localised.to_rfc3339() });
+
+    localised
 }
 
+#[derive(Debug, Clone, Serialize)]
 pub struct Warning {
     pub message: String,
     pub expected: String,
@@ -25,17 +62,32 @@ pub struct Warning {
 }
 
 enum TestType {
-    DTF,
+    Dtf,
     ReturnLoss,
 }
 
 impl SweepReport {
 
-    pub fn from_raw_ipa_report(input: raw_ipa_report::Bundle) -> Result<(SweepReport, Vec<Warning>), String> {
+    ///`source` is the export the assets are read from, `tz` the timezone the instrument clock was set to.
+    pub fn from_raw_ipa_report(input: raw_ipa_report::Bundle, source: &Input, tz: Tz) -> Result<(SweepReport, Vec<Warning>), String> {
 
         //Create Vector for parse warnings
         let mut warnings: Vec<Warning> = Vec::new();
 
+        if !input.is_tested_version() {
+            warnings.push(Warning { 
+                message: "Untested report version".to_string(), 
+                expected: raw_ipa_report::TESTED_VERSIONS.join("/"), 
+                result: input.Version.clone() });
+        }
+
+        for path in input.unrecognised_elements() {
+            warnings.push(Warning { 
+                message: "Unrecognised element kept in extensions".to_string(), 
+                expected: "None".to_string(), 
+                result: path });
+        }
+
         //Create Vector for the testing devices
         let mut tmp_devices: Vec<Device> = Vec::new();
 
@@ -43,7 +95,6 @@ impl SweepReport {
             for device in input.Devices.Device.clone() {
 
                 let details_vec = device.Details.DeviceDetails;
-                let item;
                 
                 if details_vec.is_empty() {
                     return Err("Empty device details vector".to_string())
@@ -56,7 +107,7 @@ impl SweepReport {
                         result: details_vec.len().to_string() });
                 }
 
-                item = details_vec.get(0).unwrap();
+                let item = details_vec.first().unwrap();
                 
 
                 let tmp_device: Device = Device { 
@@ -64,7 +115,7 @@ impl SweepReport {
                     model: (device.Model), 
                     ID: (item.ID.clone()), 
                     sw_version: (item.SWVersions.clone()), 
-                    calibration_date: (DateTime::parse_from_rfc3339(item.CalDate.as_str()).expect("Time Parse Error")), 
+                    calibration_date: (DateTime::parse_from_rfc3339(item.CalDate.as_str()).map_err(|e| format!("Invalid CalDate {}: {}", item.CalDate, e))?.with_timezone(&tz)), 
                     signature: (item.Signature.clone()) 
                 };
 
@@ -73,90 +124,74 @@ impl SweepReport {
 
             let mut tmp_reports: Vec<Report> = Vec::new();
 
-            for test in input.Reports.Report.get(0).unwrap().Items.Test.clone() {
+            let tests = input.Reports.Report.first().ok_or("No Report in Reports")?.Items.Test.clone();
+
+            //reading and parsing the CSVs is most of the work, so it is spread over threads up front
+            let traces: Vec<Option<Result<CsvData, String>>> = parallel::map(&tests, |test| {
+                let csv_path = test.Assets.Asset.iter().rfind(|asset| asset.ends_with(".csv"))?;
+                let is_dtf = test.Results.TestResult.Unit.parse::<Unit>() == Ok(Unit::Vswr);
+                Some(source.read_asset(csv_path).and_then(|data| CsvData::from_raw(&String::from_utf8_lossy(&data), is_dtf).map_err(|e| format!("{}: {}", csv_path, e))))
+            });
+
+            for (test, csv_data) in tests.into_iter().zip(traces) {
                 
                 let mut duplicate_tag: bool = false;
                
-                let test_type: TestType = match test.Results.TestResult.Unit.as_str() {
-                    "VSWR" => TestType::DTF,
-                    "dB" => TestType::ReturnLoss,
-                    _ => panic!("Unknown test type"),
-                };
-
-                let mut associated_state: Option<raw_ipa_report::State> = None;
-                let mut associated_csv_path: Option<String> = None;
+                let unit: Unit = test.Results.TestResult.Unit.parse()?;
 
-                for raw_state in input.States.State.clone() {
-                    if raw_state.ID == test.StateID{
-                        associated_state = Some(raw_state);
-                        break;
-                    }
-                }
+                let test_type: TestType = match unit {
+                    Unit::Vswr => TestType::Dtf,
+                    Unit::ReturnLoss => TestType::ReturnLoss,
+                    _ => return Err(format!("Unknown test type on test {}: {}", test.ID, test.Results.TestResult.Unit)),
+                };
 
-                if associated_state.is_none() {
-                    panic!("No associated state found.")
-                }
+                let associated_state: raw_ipa_report::State = input.States.State.iter()
+                    .find(|raw_state| raw_state.ID == test.StateID)
+                    .cloned()
+                    .ok_or(format!("No State {} for test {}", test.StateID, test.ID))?;
 
-                for asset in test.Assets.Asset {
-                    if asset.ends_with(".csv"){
-                        associated_csv_path = Some(asset);
-                    }
-                }
+                let CsvData { trace, marker } = csv_data.ok_or(format!("No CSV linked to test {}", test.ID))??;
 
-                if associated_csv_path.is_none(){
-                    panic!("No associated CSV file linked in report")
-                }
-
-                let max: Vec<&str> = test.Results.TestResult.Maximum.split(':').collect();
-                let min: Vec<&str> = test.Results.TestResult.Minimum.split(':').collect();
+                let tag = match test.Tags.as_slice() {
+                    [tag] => tag.Tag.clone(),
+                    [] => return Err(format!("No tag on test {}, check the report", test.ID)),
+                    _ => return Err(format!("More than one tag on test {}, check the report", test.ID)),
+                };
 
                     let tmp_result: TestResult = TestResult { 
+                        id: test.ID.clone(),
                         measurement_type: test.Results.TestResult.MeasurementType, 
-                        unit: test.Results.TestResult.Unit, 
-                        p1: test.Results.TestResult.P1.parse().unwrap(), 
-                        p2: test.Results.TestResult.P2.parse().unwrap(), 
-                        max: (max.first().unwrap().to_string().parse().unwrap(), max.get(1).unwrap().to_string().parse().unwrap()), 
-                        min: (min.first().unwrap().to_string().parse().unwrap(), min.get(1).unwrap().to_string().parse().unwrap()), 
-                        avg: test.Results.TestResult.Average.parse().unwrap(), 
-                        ripple: test.Results.TestResult.Ripple.parse().unwrap(), 
-                        pass: test.Results.TestResult.Pass.parse().unwrap(),
-                        calibrated: test.Calibrated.parse().unwrap(),
-                        time: Utc.datetime_from_str(&test.Time, "%Y-%m-%d %H:%M").expect("Invalid time format").naive_local(),
+                        unit, 
+                        p1: parse_field("P1", &test.Results.TestResult.P1)?, 
+                        p2: parse_field("P2", &test.Results.TestResult.P2)?, 
+                        max: raw_ipa_report::parse_pair(&test.Results.TestResult.Maximum)?, 
+                        min: raw_ipa_report::parse_pair(&test.Results.TestResult.Minimum)?, 
+                        avg: parse_field("Average", &test.Results.TestResult.Average)?, 
+                        ripple: parse_field("Ripple", &test.Results.TestResult.Ripple)?, 
+                        pass: parse_field("Pass", &test.Results.TestResult.Pass)?,
+                        calibrated: raw_ipa_report::parse_time_of_day(&test.Calibrated)?,
+                        time: localise(tz, raw_ipa_report::parse_time(&test.Time)?, &mut warnings),
+                        device_ids: test.Devices.as_ref().map(|e| e.Device.iter().map(|device| device.ID.clone()).collect()).unwrap_or_default(),
                     };
 
                 for tmp_test in &mut tmp_reports {
 
-                    if test.Tags.is_empty() {
-                        panic!("No tags found on cable, Check Report.")
-                    }
-                    if test.Tags.len() > 1 {
-                        panic!("Too many tags found on cable, Check Report.")
-                    }
-
-                    if test.Tags.get(0).unwrap().Tag == tmp_test.tag {
+                    if tag == tmp_test.tag {
                         duplicate_tag = true;
- 
-                        for raw_state in input.States.State.clone() {
-                            if raw_state.ID == test.StateID{
-                                associated_state = Some(raw_state);
-                                break;
-                            }
-                        }
-                        if associated_state.is_none() {
-                            panic!("No associated state found.")
-                        }
 
                         match test_type {
-                            TestType::DTF => {
+                            TestType::Dtf => {
                                 tmp_test.dtf_state_id = Some(test.StateID.clone());
-                                tmp_test.dtf_state = Some(DtfState::from_raw(associated_state.clone().unwrap()));
+                                tmp_test.dtf_state = Some(DtfState::from_raw(associated_state.clone())?);
                                 tmp_test.dtf_result = Some(tmp_result.clone());
-                                tmp_test.dtf_marker = Some(read_csv_marker_position(associated_csv_path.clone().unwrap()))
+                                tmp_test.dtf_marker = marker;
+                                tmp_test.dtf_trace = Some(trace.clone());
                             },
                             TestType::ReturnLoss => {
                                 tmp_test.rl_state_id = Some(test.StateID.clone());
-                                tmp_test.rl_state = Some(RlState::from_raw(associated_state.clone().unwrap()));
+                                tmp_test.rl_state = Some(RlState::from_raw(associated_state.clone())?);
                                 tmp_test.rl_result = Some(tmp_result.clone());
+                                tmp_test.rl_trace = Some(trace.clone());
                             },
                         }
                     }
@@ -164,29 +199,33 @@ impl SweepReport {
 
                 if !duplicate_tag {
                     match test_type {
-                        TestType::DTF => {
+                        TestType::Dtf => {
                             let tmp_report: Report = Report { 
-                                tag: test.Tags.get(0).unwrap().Tag.clone(), 
+                                tag: tag.clone(), 
                                 dtf_state_id: Some(test.StateID), 
                                 rl_state_id: None, 
-                                dtf_state: Some(DtfState::from_raw(associated_state.unwrap())), 
+                                dtf_state: Some(DtfState::from_raw(associated_state)?), 
                                 rl_state: None, 
-                                dtf_marker: Some(read_csv_marker_position(associated_csv_path.unwrap())),
+                                dtf_marker: marker,
                                 dtf_result: Some(tmp_result),
-                                rl_result: None
+                                rl_result: None,
+                                dtf_trace: Some(trace),
+                                rl_trace: None
                                 };
                                 tmp_reports.push(tmp_report)
                         },
                         TestType::ReturnLoss => {
                             let tmp_report: Report = Report { 
-                                tag: test.Tags.get(0).unwrap().Tag.clone(), 
+                                tag: tag.clone(), 
                                 dtf_state_id: None, 
                                 rl_state_id: Some(test.StateID), 
                                 dtf_state: None, 
-                                rl_state: Some(RlState::from_raw(associated_state.unwrap())), 
+                                rl_state: Some(RlState::from_raw(associated_state)?), 
                                 dtf_marker: None,
                                 dtf_result: None,
-                                rl_result: Some(tmp_result)
+                                rl_result: Some(tmp_result),
+                                dtf_trace: None,
+                                rl_trace: Some(trace)
                                 };
                                 tmp_reports.push(tmp_report)
                         },
@@ -194,7 +233,7 @@ impl SweepReport {
                 }
             }
 
-        let sweep_report: SweepReport = SweepReport { devices: (tmp_devices), reports: (tmp_reports) };
+        let sweep_report: SweepReport = SweepReport { version: (input.Version.trim().to_string()), devices: (tmp_devices), reports: (tmp_reports) };
 
         Ok((sweep_report, warnings))
     }
@@ -202,18 +241,52 @@ impl SweepReport {
 
 #[derive(Debug)]
 pub struct SweepReport {
+    pub version: String,
     pub devices: Vec<Device>,
     pub reports: Vec<Report>
 
 }
-    #[derive(Debug)]
+
+impl SweepReport {
+    ///Devices that ran a test. Tests that don't list their devices are taken to have used all of them.
+    pub fn devices_for_test(&self, result: &TestResult) -> Vec<&Device> {
+        match result.device_ids.is_empty() {
+            true => self.devices.iter().collect(),
+            false => self.devices.iter().filter(|device| result.device_ids.contains(&device.ID)).collect(),
+        }
+    }
+
+    ///Devices used to test a cable. Tests that don't list their devices are taken to have used all of them.
+    pub fn devices_for(&self, report: &Report) -> Vec<&Device> {
+        let ids: Vec<&String> = report.results().iter().flat_map(|(_, result)| &result.device_ids).collect();
+        match ids.is_empty() {
+            true => self.devices.iter().collect(),
+            false => self.devices.iter().filter(|device| ids.contains(&&device.ID)).collect(),
+        }
+    }
+}
+    #[derive(Debug, Clone)]
+    #[allow(non_snake_case)]
     pub struct Device {
         pub serial_number: String,
         pub model: String,
         pub ID: String,
         pub sw_version: String,
-        pub calibration_date: DateTime<FixedOffset>,
-        pub signature: String
+        pub calibration_date: DateTime<Tz>,
+        pub signature: Option<String>
+    }
+
+    impl Device {
+        ///End of the factory calibration interval.
+        pub fn calibration_due(&self, interval_months: u32) -> DateTime<Tz> {
+            //only an absurd interval overflows, and that never runs out
+            self.calibration_date.checked_add_months(Months::new(interval_months))
+                .unwrap_or_else(|| DateTime::<chrono::Utc>::MAX_UTC.with_timezone(&self.calibration_date.timezone()))
+        }
+
+        pub fn calibration_valid_at(&self, time: &DateTime<Tz>, interval_months: u32) -> bool {
+            *time <= self.calibration_due(interval_months)
+        }
     }
 
     #[derive(Debug, Clone)]
@@ -226,33 +299,86 @@ pub struct SweepReport {
         pub dtf_marker: Option<f64>, 
         pub dtf_result: Option<TestResult>,
         pub rl_result: Option<TestResult>,
+        pub dtf_trace: Option<Trace>,
+        pub rl_trace: Option<Trace>,
+    }
+
+    impl Report {
+        ///The DTF and RL results present for this tag, labelled by test type.
+        pub fn results(&self) -> Vec<(&'static str, &TestResult)> {
+            let mut results: Vec<(&'static str, &TestResult)> = Vec::new();
+            if let Some(result) = &self.dtf_result {
+                results.push(("DTF", result));
+            }
+            if let Some(result) = &self.rl_result {
+                results.push(("RL", result));
+            }
+            results
+        }
+
+        ///Passes when every test on this tag passed, `None` when it has no results.
+        pub fn passed(&self) -> Option<bool> {
+            let results = self.results();
+            match results.is_empty() {
+                true => None,
+                false => Some(results.iter().all(|(_, result)| result.pass)),
+            }
+        }
+
+        ///Headroom of the worst point inside the limit in dB of return loss, the smallest over the
+        ///DTF and RL tests. The worst point is the highest for an upper limit and the lowest for a
+        ///lower one. Negative when a test is outside its limit, `None` when no test has a limit.
+        pub fn margin(&self) -> Option<f64> {
+            let limits = [
+                (self.dtf_result.as_ref(), self.dtf_state.as_ref().map(|e| &e.limit)),
+                (self.rl_result.as_ref(), self.rl_state.as_ref().map(|e| &e.limit)),
+            ];
+
+            limits.into_iter()
+                .filter_map(|(result, limit)| {
+                    let (result, limit) = (result?, limit?);
+                    let value = limit.value_in(Unit::ReturnLoss)?;
+                    //every unit converts to return loss in the same order, so the sides stay put
+                    match limit.is_lower() {
+                        true => Some(metrics::convert(result.min.1, result.unit, Unit::ReturnLoss) - value),
+                        false => Some(value - metrics::convert(result.max.1, result.unit, Unit::ReturnLoss)),
+                    }
+                })
+                .min_by(|a, b| a.total_cmp(b))
+        }
+
+        ///Time of the first test on this tag.
+        pub fn time(&self) -> Option<DateTime<Tz>> {
+            self.results().iter().map(|(_, result)| result.time).min()
+        }
     }
 
         impl DtfState {
-            pub fn from_raw(input: raw_ipa_report::State) -> DtfState {
-                let input_clone = input.clone().Rx_kHz.unwrap();
-                let rx_khz: Vec<&str> = input_clone.split(':').collect();
-                let limit = input.Limits.Limit.get(0).unwrap().clone();
+            pub fn from_raw(input: raw_ipa_report::State) -> Result<DtfState, String> {
+                let rx_khz = raw_ipa_report::parse_pair(input.Rx_kHz.as_deref().ok_or(format!("No Rx_kHz on DTF State {}", input.ID))?)?;
+                let limit = input.Limits.Limit.first().ok_or(format!("No Limit on State {}", input.ID))?.clone();
 
                 let result: DtfState = DtfState { 
-                    id: input.ID, 
+                    id: input.ID.clone(), 
                     test_type: input.TestType, 
-                    rx_khz: (rx_khz.first().unwrap().to_string().parse().unwrap(), rx_khz.get(1).unwrap().to_string().parse().unwrap() ), 
-                    points: input.Points.parse().unwrap(), 
-                    limit_distance: input.Distance_m.unwrap().parse().unwrap(), 
-                    cable_loss_dbm: input.CableLoss_dB_per_m.unwrap().parse().unwrap(), 
+                    rx_khz, 
+                    points: parse_field("Points", &input.Points)?, 
+                    limit_distance: parse_field("Distance_m", input.Distance_m.as_deref().ok_or(format!("No Distance_m on DTF State {}", input.ID))?)?, 
+                    cable_loss_dbm: parse_field("CableLoss_dB_per_m", input.CableLoss_dB_per_m.as_deref().ok_or(format!("No CableLoss_dB_per_m on DTF State {}", input.ID))?)?, 
+                    vf: input.VF.as_deref().map(|vf| parse_field("VF", vf)).transpose()?, 
                     limit: Limit { 
                         limit_type: limit.Type, 
                         measurement_type: limit.MeasurementType, 
-                        unit: limit.Unit, 
+                        unit: limit.Unit.parse()?, 
                         name: limit.Name, 
                         reference_value: limit.Reference} 
                 };
-                result
+                Ok(result)
             }
         }
 
         #[derive(Debug, Clone)]
+        #[allow(dead_code)]
         pub struct DtfState {
             pub id: String,
             pub test_type: String,
@@ -260,54 +386,80 @@ pub struct SweepReport {
             pub points: u64,
             pub limit_distance: u64,
             pub cable_loss_dbm: f64,
+            ///velocity factor the tester was set to
+            pub vf: Option<f64>,
 
             pub limit: Limit
         }
 
         impl RlState {
-            pub fn from_raw(input: raw_ipa_report::State) -> RlState { 
+            pub fn from_raw(input: raw_ipa_report::State) -> Result<RlState, String> { 
 
-                let limit = input.Limits.Limit.get(0).unwrap().clone();
+                let limit = input.Limits.Limit.first().ok_or(format!("No Limit on State {}", input.ID))?.clone();
+                let rx_khz = match &input.Rx_kHz {
+                    Some(e) => Some(raw_ipa_report::parse_pair(e)?),
+                    None => None,
+                };
 
                 let result: RlState = RlState { 
                     id: input.ID, 
                     test_type: input.TestType, 
-                    points: input.Points.parse().unwrap(), 
+                    rx_khz, 
+                    points: parse_field("Points", &input.Points)?, 
                     limit: Limit { 
                         limit_type: limit.Type, 
                         measurement_type: limit.MeasurementType, 
-                        unit: limit.Unit, 
+                        unit: limit.Unit.parse()?, 
                         name: limit.Name, 
                         reference_value: limit.Reference 
                     } 
                 };
 
-                result
+                Ok(result)
             }
         }
 
         #[derive(Debug, Clone)]
+        #[allow(dead_code)]
         pub struct RlState {
             pub id: String, 
             pub test_type: String,
+            pub rx_khz: Option<(u64,u64)>,
             pub points: u64,
 
             pub limit: Limit
         }
     
             #[derive(Debug, Clone)]
+            #[allow(dead_code)]
             pub struct Limit {
+                ///"Upper" or "Lower", which side of the reference a pass is on
                 pub limit_type: String,
                 pub measurement_type: String,
-                pub unit: String,
+                pub unit: Unit,
                 pub name: String,
-                pub reference_value: String
+                pub reference_value: Option<String>
+            }
+
+            impl Limit {
+                ///Whether results have to stay above the reference rather than below it.
+                pub fn is_lower(&self) -> bool {
+                    self.limit_type.trim().eq_ignore_ascii_case("lower")
+                }
+
+                ///The reference value converted to `unit`, if it is a number.
+                pub fn value_in(&self, unit: Unit) -> Option<f64> {
+                    let value: f64 = self.reference_value.as_ref()?.trim().parse().ok()?;
+                    Some(metrics::convert(value, self.unit, unit))
+                }
             }
 
         #[derive(Debug, Clone)]
         pub struct TestResult {
+            ///ID of the test, numbered from 1 in every export
+            pub id: String,
             pub measurement_type: String,
-            pub unit: String,
+            pub unit: Unit,
             pub p1: u32,
             pub p2: u32,
             pub max: (f64, f64),
@@ -315,6 +467,137 @@ pub struct SweepReport {
             pub avg: f64,
             pub ripple: f64,
             pub pass: bool,
-            pub calibrated: chrono::NaiveTime,
-            pub time: chrono::NaiveDateTime
+            ///wall clock time of day of the field calibration, the export doesn't record its date
+            pub calibrated: NaiveTime,
+            pub time: DateTime<Tz>,
+            ///IDs of the devices the test lists, empty on firmware that doesn't record them
+            pub device_ids: Vec<String>,
         }
+
+        impl TestResult {
+            ///Time from the field calibration to the test. A calibration time later in the day than the
+            ///test means it was done the day before.
+            pub fn calibration_age(&self) -> Duration {
+                let age = self.time.time() - self.calibrated;
+                match age < Duration::zero() {
+                    true => age + Duration::days(1),
+                    false => age,
+                }
+            }
+
+            ///When the field calibration used for this test was done.
+            pub fn calibrated_at(&self) -> DateTime<Tz> {
+                self.time - self.calibration_age()
+            }
+        }
+
+///The fixtures/v1.0 export, read with the instrument clock in `tz`, for the tests of every module.
+#[cfg(test)]
+pub(crate) fn fixture_report(tz: Tz) -> (SweepReport, Vec<Warning>) {
+    let source = Input::open("fixtures/v1.0").unwrap();
+    let bundle = raw_ipa_report::raw_report_from_str(source.report_xml()).unwrap();
+    SweepReport::from_raw_ipa_report(bundle, &source, tz).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> (SweepReport, Vec<Warning>) {
+        fixture_report(Tz::UTC)
+    }
+
+    #[test]
+    fn parses_the_v1_0_fixture() {
+        let (report, warnings) = fixture();
+
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(report.version, "1.0");
+        assert_eq!(report.devices.len(), 1);
+        assert_eq!(report.devices[0].serial_number, "IE118500");
+
+        let tags: Vec<&str> = report.reports.iter().map(|e| e.tag.as_str()).collect();
+        assert_eq!(tags, ["F-L29-226", "F-L29-227", "F-L29-228", "F-L29-9", "F-L29-10"]);
+
+        let cable = &report.reports[0];
+        assert_eq!(cable.dtf_marker, Some(26.89));
+        assert_eq!(cable.dtf_result.as_ref().unwrap().max, (26.9, 1.325));
+        assert_eq!(cable.rl_result.as_ref().unwrap().max, (1920.0, -28.03));
+        assert_eq!(cable.dtf_state.as_ref().unwrap().vf, Some(0.88));
+        assert_eq!(cable.dtf_trace.as_ref().unwrap().points.len(), 401);
+        assert_eq!(cable.rl_trace.as_ref().unwrap().points.len(), 201);
+        assert_eq!(cable.time().unwrap().to_rfc3339(), "2023-03-20T13:30:00+00:00");
+    }
+
+    #[test]
+    fn margin_is_taken_on_the_side_of_the_limit() {
+        let mut cable = fixture().0.reports.remove(0);
+        cable.dtf_state = None;
+
+        //RL worst point -28.03 against an upper limit of -18
+        assert!((cable.margin().unwrap() - 10.03).abs() < 1e-9);
+
+        let state = cable.rl_state.as_mut().unwrap();
+        assert_eq!(state.limit.limit_type, "Upper");
+        state.limit.limit_type = "Lower".to_string();
+        let min = cable.rl_result.as_ref().unwrap().min.1;
+
+        assert!((cable.margin().unwrap() - (min + 18.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn malformed_fields_are_errors() {
+        let source = Input::open("fixtures/v1.0").unwrap();
+
+        for (field, broken) in [("<P1>0</P1>", "<P1>x</P1>"), ("<StateID>2</StateID>", "<StateID>9</StateID>"), ("<Unit>dB</Unit>", "<Unit>W</Unit>")] {
+            let bundle = raw_ipa_report::raw_report_from_str(source.report_xml().replacen(field, broken, 1)).unwrap();
+            assert!(SweepReport::from_raw_ipa_report(bundle, &source, Tz::UTC).is_err(), "{}", broken);
+        }
+    }
+
+    fn result(time: &str, calibrated: &str) -> TestResult {
+        let mut warnings = Vec::new();
+        TestResult {
+            id: "1".to_string(),
+            measurement_type: "DTF".to_string(),
+            unit: Unit::Vswr,
+            p1: 0,
+            p2: 400,
+            max: (0.0, 1.0),
+            min: (0.0, 1.0),
+            avg: 1.0,
+            ripple: 0.0,
+            pass: true,
+            calibrated: NaiveTime::parse_from_str(calibrated, "%H:%M").unwrap(),
+            time: localise(Tz::UTC, chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap(), &mut warnings),
+            device_ids: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn calibration_age_is_measured_back_from_the_test() {
+        let result = result("2023-03-20 13:30", "04:02");
+
+        assert_eq!(result.calibration_age(), Duration::minutes(9 * 60 + 28));
+        assert_eq!(result.calibrated_at().to_rfc3339(), "2023-03-20T04:02:00+00:00");
+    }
+
+    #[test]
+    fn calibration_later_in_the_day_than_the_test_was_the_day_before() {
+        let result = result("2023-03-20 00:30", "23:45");
+
+        assert_eq!(result.calibration_age(), Duration::minutes(45));
+        assert_eq!(result.calibrated_at().to_rfc3339(), "2023-03-19T23:45:00+00:00");
+    }
+
+    #[test]
+    fn times_skipped_by_daylight_saving_warn_instead_of_failing() {
+        let mut warnings = Vec::new();
+        let time = chrono::NaiveDateTime::parse_from_str("2023-10-01 02:30", "%Y-%m-%d %H:%M").unwrap();
+
+        let localised = localise(chrono_tz::Australia::Sydney, time, &mut warnings);
+
+        assert_eq!(localised.to_rfc3339(), "2023-10-01T03:30:00+11:00");
+        assert_eq!(warnings.len(), 1);
+    }
+}
@@ -1,75 +1,1056 @@
-use std::{fs, time};
+use std::{collections::BTreeMap, env, fs, path::{Path, PathBuf}, thread, time};
 
-use chrono::Timelike;
+use chrono::Duration;
+use chrono_tz::Tz;
 use math::round;
-use owo_colors::OwoColorize;
-use argh::FromArgs;
+use crate::color::Colorize;
+use cli::*;
+use input::Input;
 
 
+mod batch;
+mod cables;
+mod checks;
+mod cli;
+mod color;
+mod compare;
+mod database;
+mod diff;
+mod dtf;
+mod exit;
+mod export;
+mod input;
 mod ipa_report;
+mod manifest;
+mod metrics;
+mod parallel;
+mod plot;
+mod progress;
 mod raw_ipa_report;
-
-#[derive(FromArgs)]
-///Parser for Kaelus Sweep Tester Reports.
-struct Arguments {
-    ///path of Report.xml
-    #[argh(positional)]
-    path: String,
-    ///sort the cables by tag
-    #[argh(switch, short = 's')]
-    sort: bool,
-    ///displays extra information
-    #[argh(switch, short = 'v')]
-    verbose: bool
-}
+mod render;
+mod schedule;
+mod serve;
+mod sort;
+mod table;
+mod trace;
+mod verify;
 
 fn main() {
 
+    restore_sigpipe();
+
     let args: Arguments = argh::from_env();
+    color::init(args.color);
+
+    let tz = resolve_tz(args.tz).unwrap_or_else(|e| exit::fail(e));
+    let processing = Processing::checks(args.max_cal_age, args.marker_tolerance, args.cal_interval)
+        .recompute(args.recompute_vf, args.cable_loss, args.vf_tags);
+    let globals = Globals { tz, quiet: args.quiet, strict: args.strict, fail_on_expired_cal: args.fail_on_expired_cal, processing };
+
+    if let Some(jobs) = args.jobs {
+        if jobs == 0 {
+            exit::fail(exit::Failure::usage("--jobs must be at least 1".to_string()));
+        }
+        parallel::set_threads(jobs);
+    }
+
+    match args.command {
+        Command::Summary(command) => {
+            let processing = globals.processing.clone().sorted(sort_keys(command.sort, command.sort_by), command.desc);
+            let columns = command.columns.unwrap_or_default();
+            match command.markdown {
+                true => markdown(&command.path, &globals, &processing, command.unit, &columns.0),
+                false => show(&command.path, &globals, &processing, Some(&columns.0), command.unit),
+            }
+        },
+        Command::Detail(command) => {
+            let processing = globals.processing.clone().sorted(sort_keys(command.sort, command.sort_by), command.desc);
+            show(&command.path, &globals, &processing, None, command.unit)
+        },
+        Command::Export(command) => export(command, &globals),
+        Command::Validate(command) => validate(command, &globals),
+        Command::Reconcile(command) => reconcile(command, &globals),
+        Command::Diff(command) => diff(command, &globals),
+        Command::Batch(command) => batch(command, &globals),
+        Command::Watch(command) => watch(command, &globals),
+        Command::Ingest(command) => ingest(command, &globals),
+        Command::Query(command) => query(command),
+        Command::Serve(command) => serve(command, &globals),
+        Command::Plot(command) => plot(command, &globals),
+        Command::Verify(command) => verify(command, &globals),
+        Command::CheckManifest(command) => check_manifest(command),
+        Command::Events(command) => events(command, &globals),
+        Command::Render(command) => render(command, &globals),
+        Command::Compare(command) => compare(command, &globals),
+    }
+}
+
+///Rust ignores SIGPIPE, so printing into `head` that has already exited panics. Take the default
+///back and end quietly like other command line tools. The standard library's sockets don't raise it,
+///so `serve` isn't affected.
+#[cfg(unix)]
+fn restore_sigpipe() {
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+}
+
+#[cfg(not(unix))]
+fn restore_sigpipe() {}
+
+///Options given before the command.
+struct Globals {
+    tz: Tz,
+    quiet: bool,
+    strict: bool,
+    fail_on_expired_cal: bool,
+    ///the checks and recompute options, commands add their sort order
+    processing: Processing,
+}
+
+///What is done to a report after it is loaded and before it is shown or exported.
+#[derive(Clone)]
+struct Processing {
+    ///`None` skips the field calibration, marker and factory calibration checks
+    max_cal_age: Option<i64>,
+    length_tolerance: f64,
+    cal_interval: u32,
+    recompute_vf: Option<String>,
+    cable_loss: Option<f64>,
+    vf_tags: Option<String>,
+    ///empty keeps report order
+    sort_by: Vec<sort::SortKey>,
+    descending: bool,
+}
+
+impl Processing {
+
+    fn checks(max_cal_age: i64, length_tolerance: f64, cal_interval: u32) -> Processing {
+        Processing { max_cal_age: Some(max_cal_age), length_tolerance, cal_interval, recompute_vf: None, cable_loss: None, vf_tags: None, sort_by: Vec::new(), descending: false }
+    }
+
+    fn without_checks(self) -> Processing {
+        Processing { max_cal_age: None, ..self }
+    }
+
+    fn recompute(self, recompute_vf: Option<String>, cable_loss: Option<f64>, vf_tags: Option<String>) -> Processing {
+        Processing { recompute_vf, cable_loss, vf_tags, ..self }
+    }
+
+    fn sorted(self, sort_by: Vec<sort::SortKey>, descending: bool) -> Processing {
+        Processing { sort_by, descending, ..self }
+    }
+}
+
+///`-s` is shorthand for `--sort-by tag`.
+fn sort_keys(sort: bool, sort_by: Option<sort::SortKeys>) -> Vec<sort::SortKey> {
+    match (sort_by, sort) {
+        (Some(keys), _) => keys.0,
+        (None, true) => vec![sort::SortKey::Tag],
+        (None, false) => Vec::new(),
+    }
+}
+
+///A loaded and processed export.
+struct Loaded {
+    source: Input,
+    assets: Vec<String>,
+    report: ipa_report::SweepReport,
+    warnings: Vec<ipa_report::Warning>,
+    any_expired_cal: bool,
+}
+
+fn resolve_tz(name: Option<String>) -> Result<Tz, exit::Failure> {
+    match name.or(env::var("KAELUS_TZ").ok()) {
+        Some(name) => name.parse().map_err(|_| exit::Failure::parse(format!("Unknown timezone: {}", name))),
+        None => Ok(Tz::UTC),
+    }
+}
+
+fn load_report(path: &str, globals: &Globals) -> (ipa_report::SweepReport, Vec<ipa_report::Warning>) {
+    let loaded = load(path, globals.tz, &globals.processing.clone().without_checks(), None).unwrap_or_else(|e| exit::fail(e));
+    (loaded.report, loaded.warnings)
+}
+
+///The common input loader: reads the export, applies `processing` and collects the warnings.
+///Given a start time the Start/Parsed/Processed timings are printed as it goes.
+fn load(path: &str, tz: Tz, processing: &Processing, start: Option<time::Instant>) -> Result<Loaded, exit::Failure> {
+
+    let timing = |stage: &str| if let Some(start) = start {
+        print!("{}:({})", stage, format!("{}ms", start.elapsed().as_millis()).red());
+    };
+
+    timing("\nStart");
 
-    //set a starting time
-    let start = time::Instant::now();
-    print!("\nStart:({})", format!("{}ms", start.elapsed().as_millis()).red());
- 
     //read in raw dara from file
-    let data = fs::read_to_string(args.path).unwrap();  
-    let raw_report = raw_ipa_report::raw_report_from_str(remove_non_ascii(data)).unwrap();
-    print!(", Parsed:({})", format!("{}ms", start.elapsed().as_millis()).red());
+    let source = Input::open(path).map_err(exit::Failure::parse)?;
+    let raw_report = raw_ipa_report::raw_report_from_str(remove_non_ascii(source.report_xml()))
+        .map_err(|e| exit::Failure::parse(format!("{}: {}", path, e)))?;
+    let assets = raw_report.assets();
+    timing(", Parsed");
 
-    let (formatted_report, warnings) = ipa_report::SweepReport::from_raw_ipa_report(raw_report).unwrap();
-    print!(", Processed:({}), ", format!("{}ms", start.elapsed().as_millis()).red());
+    if let Some(asset) = assets.iter().find(|asset| input::relative_path(Path::new(""), asset).is_err()) {
+        return Err(exit::Failure::parse(format!("{}: {} is not a path inside the export", path, asset)));
+    }
 
-    let mut sum: f64 = 0.0;
+    //every trace is needed, missing thumbnails don't matter
+    if let Some(asset) = assets.iter().find(|asset| asset.to_ascii_lowercase().ends_with(".csv") && !source.has_asset(asset)) {
+        return Err(exit::Failure::asset_missing(format!("{}: {} is referenced but not in the export", path, asset)));
+    }
 
-    for report in formatted_report.reports.clone(){
-        if report.dtf_marker.is_some(){
-            sum = sum + report.dtf_marker.unwrap();
+    let (mut report, mut warnings) = ipa_report::SweepReport::from_raw_ipa_report(raw_report, &source, tz)
+        .map_err(|e| exit::Failure::parse(format!("{}: {}", path, e)))?;
+
+    if let Some(cable) = &processing.recompute_vf {
+        let (vf, loss) = match (cable.parse::<f64>(), cables::find(cable)) {
+            (Ok(vf), _) => (vf, processing.cable_loss),
+            (_, Some(cable)) => (cable.velocity_factor, processing.cable_loss.or(Some(cable.loss_db_per_m))),
+            _ => return Err(exit::Failure::usage(format!("Unknown cable type {}, expected a VF or one of: {}", cable, cables::CATALOGUE.map(|cable| format!("{} ({})", cable.name, cable.description)).join(", ")))),
+        };
+        if !vf.is_finite() || vf <= 0.0 {
+            return Err(exit::Failure::usage(format!("Invalid velocity factor {}, expected a number above 0", vf)));
+        }
+        if let Some(loss) = loss.filter(|loss| !loss.is_finite() || *loss < 0.0) {
+            return Err(exit::Failure::usage(format!("Invalid cable loss {}, expected dB/m of 0 or more", loss)));
+        }
+        let tags: Option<Vec<&str>> = processing.vf_tags.as_ref().map(|tags| tags.split(',').map(|tag| tag.trim()).collect());
+
+        for report in report.reports.iter_mut() {
+            if report.dtf_state.is_some() && tags.as_ref().is_none_or(|tags| tags.contains(&report.tag.as_str())) {
+                dtf::recompute(report, vf, loss).map_err(|e| exit::Failure::parse(format!("{}: {}", path, e)))?;
+            }
         }
+
+        //a mistyped tag would otherwise leave its cable at the old VF without a word
+        for tag in tags.iter().flatten().filter(|tag| !report.reports.iter().any(|report| report.tag == **tag)) {
+            warnings.push(ipa_report::Warning { 
+                message: format!("--vf-tags names {}, which isn't in the report", tag), 
+                expected: "A tag in the report".to_string(), 
+                result: tag.to_string() });
+        }
+    }
+
+    let mut any_expired_cal = false;
+
+    if let Some(max_cal_age) = processing.max_cal_age {
+        warnings.extend(checks::field_calibration_age(&report, Duration::hours(max_cal_age)));
+
+        warnings.extend(checks::marker_position(&report, processing.length_tolerance));
+
+        let expired_cal = checks::factory_calibration(&report, processing.cal_interval);
+        any_expired_cal = !expired_cal.is_empty();
+        warnings.extend(expired_cal);
+    }
+
+    sort::sort_reports(&mut report.reports, &processing.sort_by, processing.descending);
+
+    timing(", Processed");
+
+    if start.is_some() {
+        print!(", Threads:({})", parallel::threads().red());
     }
 
-    println!("{}: ({}), {}: ({}m)","Count".green(), formatted_report.reports.len(), "Total Length".green(), round::half_away_from_zero(sum, 2));
+    Ok(Loaded { source, assets, report, warnings, any_expired_cal })
+}
 
-   
+impl Loaded {
+    ///Exit codes for failed tests and, when asked for, warnings and expired factory calibrations.
+    fn exit_codes(&self, strict: bool, fail_on_expired_cal: bool) -> Vec<exit::ExitCode> {
+        let mut codes: Vec<exit::ExitCode> = Vec::new();
+        if self.report.reports.iter().any(|report| report.passed() == Some(false)) {
+            codes.push(exit::ExitCode::TestFailed);
+        }
+        if strict && !self.warnings.is_empty() {
+            codes.push(exit::ExitCode::Warnings);
+        }
+        if fail_on_expired_cal && self.any_expired_cal {
+            codes.push(exit::ExitCode::CheckFailed);
+        }
+        codes
+    }
+}
 
-    for warning in warnings{
+fn print_warnings(warnings: &[ipa_report::Warning]) {
+    for warning in warnings {
         println!("{} ({}) ({}:{})", "WARN: ".yellow().bold(), warning.message, warning.expected, warning.result)
     }
-    
-    match args.verbose{
-        true => detailed_summary(args.sort, formatted_report),
-        false => summary(args.sort, formatted_report),
+}
+
+///Prints the banner, warnings and then the summary table in `columns`, or the detail view without them.
+fn show(path: &str, globals: &Globals, processing: &Processing, columns: Option<&[table::Column]>, unit: Option<metrics::Unit>) {
+
+    //set a starting time, unless the timings are suppressed
+    let loaded = load(path, globals.tz, processing, (!globals.quiet).then(time::Instant::now)).unwrap_or_else(|e| exit::fail(e));
+    let codes = loaded.exit_codes(globals.strict, globals.fail_on_expired_cal);
+
+    let mut sum: f64 = 0.0;
+
+    for report in loaded.report.reports.clone(){
+        if let Some(marker) = report.dtf_marker {
+            sum += marker;
+        }
+    }
+
+    println!("{}{}: ({}), {}: ({}m)", if globals.quiet { "\n" } else { ", " }, "Count".green(), loaded.report.reports.len(), "Total Length".green(), round::half_away_from_zero(sum, 2));
+
+    print_warnings(&loaded.warnings);
+
+    match columns {
+        Some(columns) => summary(unit, columns, loaded.report),
+        None => detailed_summary(unit, loaded.report),
+    }
+
+    exit::exit_with(&codes);
+}
+
+///Just the summary table, for pasting into tickets.
+fn markdown(path: &str, globals: &Globals, processing: &Processing, unit: Option<metrics::Unit>, columns: &[table::Column]) {
+
+    let loaded = load(path, globals.tz, processing, None).unwrap_or_else(|e| exit::fail(e));
+
+    for line in table::Table::from_sweep_report(&loaded.report, columns, unit).render(table::Style::Markdown) {
+        println!("{}", line);
+    }
+
+    exit::exit_with(&loaded.exit_codes(globals.strict, globals.fail_on_expired_cal));
+}
+
+fn export(command: ExportCommand, globals: &Globals) {
+
+    if command.sign_key.is_some() && !command.manifest {
+        exit::fail(exit::Failure::usage("--sign-key only signs a manifest, add --manifest".to_string()));
+    }
+
+    //the same checks as batch, so --strict fails an export for the same warnings
+    let processing = globals.processing.clone().sorted(sort_keys(false, command.sort_by), command.desc);
+    let loaded = load(&command.path, globals.tz, &processing, None).unwrap_or_else(|e| exit::fail(e));
+
+    print_warnings(&loaded.warnings);
+
+    let certificate = export::Certificate::from_sweep_report(&loaded.report, processing.cal_interval);
+    let write_failed = |e: String| exit::Failure::write(format!("{}: {}", command.out, e));
+
+    let mut generated = export::write_exports(&certificate, Path::new(&command.out)).map_err(write_failed).unwrap_or_else(|e| exit::fail(e));
+    if let Some(format) = command.charts {
+        for report in &loaded.report.reports {
+            generated.extend(render::render_report(report, &Path::new(&command.out).join("plots"), format).map_err(write_failed).unwrap_or_else(|e| exit::fail(e)));
+        }
+    }
+    for path in &generated {
+        println!("{} {}", "Exported:".green(), path.display());
+    }
+
+    if command.manifest {
+        let key = command.sign_key.as_ref().map(|key| read_key(key));
+        let manifest_path = manifest::write_package(Path::new(&command.out), &loaded.source, &loaded.assets, &generated, key.as_deref()).map_err(write_failed).unwrap_or_else(|e| exit::fail(e));
+        println!("{} {}", "Manifest:".green(), manifest_path.display());
+    }
+
+    exit::exit_with(&loaded.exit_codes(globals.strict, false));
+}
+
+fn validate(command: ValidateCommand, globals: &Globals) {
+
+    let loaded = load(&command.path, globals.tz, &globals.processing, None).unwrap_or_else(|e| exit::fail(e));
+
+    print_warnings(&loaded.warnings);
+
+    let codes = loaded.exit_codes(true, globals.fail_on_expired_cal);
+    if codes.is_empty() {
+        println!("{} {} cables, all passed, no warnings", "OK:".green().bold(), loaded.report.reports.len());
+    }
+
+    exit::exit_with(&codes);
+}
+
+fn reconcile(command: ReconcileCommand, globals: &Globals) {
+
+    let (report, _) = load_report(&command.path, globals);
+    let schedule = fs::read_to_string(&command.schedule).map_err(|e| e.to_string())
+        .and_then(|raw_csv| schedule::Schedule::from_csv(&raw_csv))
+        .unwrap_or_else(|e| exit::fail(exit::Failure::parse(format!("{}: {}", command.schedule, e))));
+
+    let results = schedule::reconcile(&schedule, &report, command.length_tolerance);
+
+    if command.json {
+        println!("{}", serde_json::to_string_pretty(&results).unwrap());
+    } else {
+        let count = |status: schedule::Status| results.iter().filter(|e| e.status == status).count();
+
+        println!("\n{}: ({}/{}), {}: ({}), {}: ({}), {}: ({}), {}: ({})\n",
+            "Complete".green(), count(schedule::Status::Complete), schedule.cables.len(),
+            "Failed".red(), count(schedule::Status::Failed),
+            "Length".yellow(), count(schedule::Status::LengthMismatch),
+            "Missing".red(), count(schedule::Status::Missing),
+            "Unexpected".yellow(), count(schedule::Status::Unexpected),
+        );
+
+        let length = |value: Option<f64>| match value {
+            Some(e) => format!("{:.2}m", e),
+            None => "N/A".to_string(),
+        };
+
+        for result in &results {
+            if result.status == schedule::Status::Complete {
+                continue;
+            }
+            println!("{} {}: {: <15} {}: {: <8} {}: {}",
+                match result.status {
+                    schedule::Status::Complete => format!("{: <10}", "Complete").green().to_string(),
+                    schedule::Status::Failed => format!("{: <10}", "Failed").red().bold().to_string(),
+                    schedule::Status::LengthMismatch => format!("{: <10}", "Length").yellow().to_string(),
+                    schedule::Status::Missing => format!("{: <10}", "Missing").red().to_string(),
+                    schedule::Status::Unexpected => format!("{: <10}", "Unexpected").yellow().to_string(),
+                },
+                "Tag".green(), result.tag,
+                "Expected".green(), length(result.expected_length),
+                "Measured".green(), length(result.measured_length),
+            );
+        }
+    }
+
+    exit::exit_with(&reconcile_exit_codes(&results));
+}
+
+///Failed cables are test failures, missing and wrong length cables leave the schedule incomplete.
+fn reconcile_exit_codes(results: &[schedule::Reconciliation]) -> Vec<exit::ExitCode> {
+    results.iter().map(|e| match e.status {
+        schedule::Status::Failed => exit::ExitCode::TestFailed,
+        schedule::Status::Missing | schedule::Status::LengthMismatch => exit::ExitCode::Incomplete,
+        schedule::Status::Complete | schedule::Status::Unexpected => exit::ExitCode::Ok,
+    }).collect()
+}
+
+fn batch(command: BatchCommand, globals: &Globals) {
+
+    let out = Path::new(&command.out);
+    fs::create_dir_all(out).unwrap_or_else(|e| exit::fail(exit::Failure::write(format!("{}: {}", command.out, e))));
+
+    //compare canonical paths, so an output directory inside the tree is skipped however it was given
+    let root = fs::canonicalize(&command.path).unwrap_or_else(|e| exit::fail(exit::Failure::parse(format!("{}: {}", command.path, e))));
+    let out = fs::canonicalize(out).unwrap_or_else(|e| exit::fail(exit::Failure::write(format!("{}: {}", command.out, e))));
+
+    let start = time::Instant::now();
+    let exports = batch::discover(&root, Some(&out)).unwrap_or_else(|e| exit::fail(exit::Failure::parse(e)));
+    let discovered = start.elapsed();
+    let processing = globals.processing.clone().sorted(vec![sort::SortKey::Tag], false);
+
+    //exports are processed across threads, then reported in path order
+    let processed: Vec<(batch::IndexRow, Vec<exit::ExitCode>)> = parallel::map(&exports, |path| {
+
+        let site = batch::site_of(&root, path);
+        let relative = path.strip_prefix(&root).unwrap_or(path).display().to_string();
+
+        //a broken export shouldn't stop the rest, the error goes in the index instead
+        match load(&path.to_string_lossy(), globals.tz, &processing, None) {
+            Ok(loaded) => {
+                let output = batch::output_dir(&root, path);
+                let certificate = export::Certificate::from_sweep_report(&loaded.report, processing.cal_interval);
+
+                match export::write_exports(&certificate, &out.join(&output)) {
+                    Ok(_) => (
+                        batch::IndexRow::from_sweep_report(site, relative, &loaded.report, loaded.warnings.len(), output.display().to_string()),
+                        loaded.exit_codes(globals.strict, false),
+                    ),
+                    Err(e) => (batch::IndexRow::from_error(site, relative, e), vec![exit::ExitCode::WriteFailed]),
+                }
+            },
+            Err(failure) => (batch::IndexRow::from_error(site, relative, failure.message), vec![failure.code]),
+        }
+    });
+
+    let mut rows: Vec<batch::IndexRow> = Vec::new();
+    let mut codes: Vec<exit::ExitCode> = Vec::new();
+
+    for (row, row_codes) in processed {
+
+        match (&row.error, row.failures) {
+            (Some(error), _) => println!("{} {}", "ERROR:".red().bold(), error),
+            (None, 0) => println!("{} {} ({} cables, {} warnings)", "OK:".green(), row.path, row.cables, row.warnings),
+            (None, failures) => println!("{} {} ({} cables, {} failed, {} warnings)", "FAIL:".red(), row.path, row.cables, failures, row.warnings),
+        }
+
+        rows.push(row);
+        codes.extend(row_codes);
+    }
+
+    let mut sites: Vec<&String> = rows.iter().map(|row| &row.site).collect();
+    sites.sort();
+    sites.dedup();
+
+    for site in sites {
+        let site_rows: Vec<batch::IndexRow> = rows.iter().filter(|row| &row.site == site).cloned().collect();
+        batch::write_index(&site_rows, &out.join(site)).unwrap_or_else(|e| exit::fail(exit::Failure::write(format!("{}: {}", site, e))));
+    }
+
+    for path in batch::write_index(&rows, &out).unwrap_or_else(|e| exit::fail(exit::Failure::write(format!("{}: {}", command.out, e)))) {
+        println!("{} {}", "Index:".green(), path.display());
+    }
+
+    if !globals.quiet {
+        print!("\nStart:({}), Discovered:({}), Processed:({}), Threads:({}), ",
+            "0ms".red(),
+            format!("{}ms", discovered.as_millis()).red(),
+            format!("{}ms", start.elapsed().as_millis()).red(),
+            parallel::threads().red(),
+        );
     }
 
+    println!("{}: ({}), {}: ({}), {}: ({})",
+        "Exports".green(), rows.len(),
+        "Failed".red(), rows.iter().filter(|row| row.failures > 0).count(),
+        "Errors".red(), rows.iter().filter(|row| row.error.is_some()).count(),
+    );
+
+    exit::exit_with(&codes);
 }
 
+fn watch(command: WatchCommand, globals: &Globals) {
+
+    let schedule = fs::read_to_string(&command.schedule).map_err(|e| e.to_string())
+        .and_then(|raw_csv| schedule::Schedule::from_csv(&raw_csv))
+        .unwrap_or_else(|e| exit::fail(exit::Failure::parse(format!("{}: {}", command.schedule, e))));
+
+    let out = Path::new(&command.out);
+    fs::create_dir_all(out).unwrap_or_else(|e| exit::fail(exit::Failure::write(format!("{}: {}", command.out, e))));
+
+    let root = fs::canonicalize(&command.path).unwrap_or_else(|e| exit::fail(exit::Failure::parse(format!("{}: {}", command.path, e))));
+    let out = fs::canonicalize(out).unwrap_or_else(|e| exit::fail(exit::Failure::write(format!("{}: {}", command.out, e))));
+
+    let processing = globals.processing.clone().without_checks();
+
+    //what each export held when it was last processed, so unchanged exports aren't parsed again
+    let mut seen: BTreeMap<PathBuf, (batch::Fingerprint, Result<Vec<ipa_report::Report>, String>)> = BTreeMap::new();
+
+    //whether progress has been written since the last change, so a failed write is retried
+    let mut written = false;
+
+    loop {
+
+        let exports = batch::discover(&root, Some(&out)).unwrap_or_else(|e| exit::fail(exit::Failure::parse(e)));
+
+        let before = seen.len();
+        seen.retain(|path, _| exports.contains(path));
+        let removed = seen.len() != before;
+
+        //an export that is still being copied in can fail to fingerprint, it is picked up on a later check
+        let changed: Vec<(PathBuf, batch::Fingerprint)> = exports.iter()
+            .filter_map(|path| Some((path.clone(), batch::fingerprint(path).ok()?)))
+            .filter(|(path, fingerprint)| seen.get(path).is_none_or(|(seen, _)| seen != fingerprint))
+            .collect();
+
+        //nothing new, so the progress report written last time still stands
+        if changed.is_empty() && !removed && written {
+            thread::sleep(time::Duration::from_secs(command.interval));
+            continue;
+        }
+
+        let processed = parallel::map(&changed, |(path, _)| {
+            load(&path.to_string_lossy(), globals.tz, &processing, None)
+                .map(|loaded| loaded.report.reports)
+                .map_err(|failure| failure.message)
+        });
+
+        for ((path, fingerprint), reports) in changed.into_iter().zip(processed) {
+            let relative = path.strip_prefix(&root).unwrap_or(&path).display().to_string();
+            match &reports {
+                Ok(reports) => println!("{} {} ({} cables)", "Processed:".green(), relative, reports.len()),
+                Err(error) => println!("{} {}: {}", "ERROR:".red().bold(), relative, error),
+            }
+            seen.insert(path, (fingerprint, reports));
+        }
+
+        //merged oldest first, so a cable retested in a later export counts by its last attempt
+        let mut reports: Vec<ipa_report::Report> = seen.values().filter_map(|(_, reports)| reports.as_ref().ok()).flatten().cloned().collect();
+        reports.sort_by_key(|report| report.time());
+        let merged = ipa_report::SweepReport { version: String::new(), devices: Vec::new(), reports };
+
+        let results = schedule::reconcile(&schedule, &merged, command.length_tolerance);
+
+        let exports: Vec<progress::ExportStatus> = seen.iter().map(|(path, (_, reports))| progress::ExportStatus {
+            path: path.strip_prefix(&root).unwrap_or(path).display().to_string(),
+            cables: reports.as_ref().map_or(0, |reports| reports.len()),
+            error: reports.as_ref().err().cloned(),
+        }).collect();
+
+        let progress = progress::Progress::new(results, exports, chrono::Utc::now());
+        //a write that fails while watching is tried again on the next check
+        written = match progress::write_progress(&progress, &out) {
+            Ok(_) => true,
+            Err(e) if command.once => exit::fail(exit::Failure::write(e)),
+            Err(e) => {
+                println!("{} {}", "ERROR:".red().bold(), e);
+                false
+            },
+        };
+
+        if !globals.quiet {
+            println!("{} ({:.0}%), {}: ({}/{}), {}: ({}), {}: ({}), {}: ({}), {}: ({})",
+                chrono::Local::now().format("%H:%M:%S"), progress.percent_complete(),
+                "Complete".green(), progress.complete, progress.scheduled,
+                "Failed".red(), progress.failed,
+                "Length".yellow(), progress.length_mismatch,
+                "Missing".red(), progress.missing,
+                "Unexpected".yellow(), progress.unexpected,
+            );
+        }
+
+        if command.once {
+            exit::exit_with(&reconcile_exit_codes(&progress.cables));
+        }
+
+        thread::sleep(time::Duration::from_secs(command.interval));
+    }
+}
+
+///The exports at `path` and the root their sites are worked out from. A directory holding Report.xml
+///is one export, named after that directory, any other directory is searched like `batch`.
+fn find_exports(path: &str) -> (PathBuf, Vec<PathBuf>) {
+
+    let path = fs::canonicalize(path).unwrap_or_else(|e| exit::fail(exit::Failure::parse(format!("{}: {}", path, e))));
+    let export = match path.is_dir() {
+        true => path.join(input::REPORT_NAME),
+        false => path.clone(),
+    };
+
+    match (path.is_dir(), export.is_file()) {
+        (true, false) => (path.clone(), batch::discover(&path, None).unwrap_or_else(|e| exit::fail(exit::Failure::parse(e)))),
+        _ if export.file_name().is_some_and(|name| name == input::REPORT_NAME) => {
+            let dir = export.parent().unwrap_or(&export);
+            (dir.parent().unwrap_or(dir).to_path_buf(), vec![export.clone()])
+        },
+        _ => (export.parent().unwrap_or(&export).to_path_buf(), vec![export.clone()]),
+    }
+}
+
+fn ingest(command: IngestCommand, globals: &Globals) {
+
+    let (root, exports) = find_exports(&command.path);
+
+    let mut database = database::Database::open(Path::new(&command.database))
+        .unwrap_or_else(|e| exit::fail(exit::Failure::write(format!("{}: {}", command.database, e))));
+
+    let processing = globals.processing.clone().without_checks();
+    let mut codes: Vec<exit::ExitCode> = Vec::new();
+
+    for export in exports {
+
+        let site = command.site.clone().unwrap_or_else(|| batch::site_of(&root, &export));
+        let relative = export.strip_prefix(&root).unwrap_or(&export).display().to_string();
+
+        let loaded = match load(&export.to_string_lossy(), globals.tz, &processing, None) {
+            Ok(loaded) => loaded,
+            Err(failure) => {
+                println!("{} {}: {}", "ERROR:".red().bold(), relative, failure.message);
+                codes.push(failure.code);
+                continue;
+            },
+        };
+
+        let ingested = database.ingest(&site, &export.to_string_lossy(), &loaded.report)
+            .unwrap_or_else(|e| exit::fail(exit::Failure::write(format!("{}: {}", command.database, e))));
+
+        println!("{} {} ({}, {} cables, {} new tests, {} updated)", "Ingested:".green(), relative, site, ingested.cables, ingested.new, ingested.updated);
+    }
+
+    exit::exit_with(&codes);
+}
+
+fn query(command: QueryCommand) {
+
+    if !Path::new(&command.database).is_file() {
+        exit::fail(exit::Failure::parse(format!("{}: no such database", command.database)));
+    }
+
+    //only reads, so anything wrong with the file is a read error rather than a failed write
+    let database = database::Database::open(Path::new(&command.database))
+        .unwrap_or_else(|e| exit::fail(exit::Failure::parse(format!("{}: {}", command.database, e))));
+    let site = command.site.as_deref();
+    let fail = |e: String| -> ! { exit::fail(exit::Failure::parse(format!("{}: {}", command.database, e))) };
+
+    let (json, table) = match command.question {
+        database::Question::Failures => {
+            let failures = database.failures(site).unwrap_or_else(|e| fail(e));
+            (serde_json::to_string_pretty(&failures).unwrap(), table::Table {
+                headers: ["Site", "Tag", "Test", "Time (UTC)", "Device", "Worst", "Passed Later"].map(String::from).to_vec(),
+                rows: failures.iter().map(|e| vec![
+                    table::Cell::plain(e.site.clone()),
+                    table::Cell::plain(e.tag.clone()),
+                    table::Cell::plain(e.test_type.clone()),
+                    table::Cell::plain(e.time.clone()),
+                    table::Cell::plain(e.device_serial.clone()),
+                    table::Cell { text: format!("{:.2} {}", e.worst, e.unit), tone: table::Tone::Bad },
+                    match e.passed_later {
+                        true => table::Cell { text: "Yes".to_string(), tone: table::Tone::Good },
+                        false => table::Cell { text: "No".to_string(), tone: table::Tone::Bad },
+                    },
+                ]).collect(),
+            })
+        },
+        database::Question::Retests => {
+            let retests = database.retests(site).unwrap_or_else(|e| fail(e));
+            (serde_json::to_string_pretty(&retests).unwrap(), table::Table {
+                headers: ["Site", "Tag", "Test", "Attempts", "First (UTC)", "Last (UTC)", "Last Result"].map(String::from).to_vec(),
+                rows: retests.iter().map(|e| vec![
+                    table::Cell::plain(e.site.clone()),
+                    table::Cell::plain(e.tag.clone()),
+                    table::Cell::plain(e.test_type.clone()),
+                    table::Cell::plain(e.attempts.to_string()),
+                    table::Cell::plain(e.first.clone()),
+                    table::Cell::plain(e.last.clone()),
+                    match e.last_passed {
+                        true => table::Cell { text: "Pass".to_string(), tone: table::Tone::Good },
+                        false => table::Cell { text: "Fail".to_string(), tone: table::Tone::Bad },
+                    },
+                ]).collect(),
+            })
+        },
+        database::Question::Cables => {
+            let totals = database.cable_totals(site).unwrap_or_else(|e| fail(e));
+            (serde_json::to_string_pretty(&totals).unwrap(), table::Table {
+                headers: ["Cable Type", "Cables", "Total Length (m)"].map(String::from).to_vec(),
+                rows: totals.iter().map(|e| vec![
+                    table::Cell::plain(e.cable_type.clone()),
+                    table::Cell::plain(e.cables.to_string()),
+                    table::Cell::plain(export::format_value(e.total_length_m)),
+                ]).collect(),
+            })
+        },
+    };
+
+    if command.json {
+        println!("{}", json);
+        return;
+    }
+
+    for line in table.render(if command.markdown { table::Style::Markdown } else { table::Style::Plain }) {
+        println!("{}", line);
+    }
+}
+
+fn serve(command: ServeCommand, globals: &Globals) {
+
+    let (root, exports) = find_exports(&command.path);
+
+    let processing = globals.processing.clone().sorted(vec![sort::SortKey::Tag], false);
+
+    let jobs: Vec<serve::Job> = parallel::map(&exports, |export| {
+
+        let site = batch::site_of(&root, export);
+        let relative = export.strip_prefix(&root).unwrap_or(export).display().to_string();
+
+        match load(&export.to_string_lossy(), globals.tz, &processing, None) {
+            Ok(loaded) => {
+                let mut row = batch::IndexRow::from_sweep_report(site, relative, &loaded.report, loaded.warnings.len(), String::new());
+                row.output = None;
+                serve::Job { row, report: Some(loaded.report), warnings: loaded.warnings }
+            },
+            Err(failure) => serve::Job { row: batch::IndexRow::from_error(site, relative, failure.message), report: None, warnings: Vec::new() },
+        }
+    });
+
+    println!("{} {} exports, {} couldn't be read", "Loaded:".green(), jobs.len(), jobs.iter().filter(|job| job.report.is_none()).count());
+    println!("{} http://{}:{}/ (Ctrl+C to stop)", "Serving:".green(), command.bind, command.port);
+
+    serve::serve(&jobs, &command.bind, command.port, processing.cal_interval).unwrap_or_else(|e| exit::fail(exit::Failure::parse(e)));
+}
+
+fn events(command: EventsCommand, globals: &Globals) {
+
+    let (report, _) = load_report(&command.path, globals);
+
+    for input in report.reports {
+
+        if command.tag.as_ref().is_some_and(|tag| *tag != input.tag) {
+            continue;
+        }
+
+        let Some(trace) = &input.dtf_trace else { continue };
+
+        let limit: Option<f64> = input.dtf_state.as_ref()
+            .and_then(|state| state.limit.value_in(metrics::Unit::Vswr));
+
+        let settings = dtf::EventSettings { 
+            threshold: command.threshold, 
+            damage_threshold: command.damage.or(limit).unwrap_or(dtf::EventSettings::default().damage_threshold), 
+            ..Default::default() 
+        };
+
+        println!("\nTag: {} (Marker: {}, Estimated end: {})\n", 
+            input.tag.bold().underline().green(),
+            match input.dtf_marker {
+                Some(e) => format!("{:.2}m", e).green().to_string(),
+                None => "N/A".red().to_string(),
+            },
+            match dtf::estimate_cable_end(trace, &settings) {
+                Some(e) => format!("{:.2}m", e).green().to_string(),
+                None => "N/A".red().to_string(),
+            }
+        );
+
+        for event in dtf::find_events(trace, input.dtf_marker, &settings) {
+            println!("  {: >7} m  VSWR: {: <6} RL: {: <8} {}",
+                format!("{:.2}", event.distance),
+                format!("{:.2}", event.vswr),
+                format!("{:.1}dB", event.return_loss),
+                match event.kind {
+                    dtf::EventKind::PossibleDamage | dtf::EventKind::BeyondEnd => event.kind.description().red().bold().to_string(),
+                    _ => event.kind.description().yellow().to_string(),
+                }
+            );
+        }
+    }
+}
+
+fn plot(command: PlotCommand, globals: &Globals) {
+
+    let (report, _) = load_report(&command.path, globals);
+    let input = report.reports.into_iter().find(|report| report.tag == command.tag)
+        .unwrap_or_else(|| exit::fail(exit::Failure::usage(format!("No cable {} in {}", command.tag, command.path))));
+
+    println!("\nTag: {}  ({} trace, {} limit, {} marker)", 
+        input.tag.bold().underline().green(), 
+        "⣀⣀".green(), 
+        "⠁⠁".red(), 
+        "⡇".yellow()
+    );
+
+    if let (Some(trace), Some(result)) = (&input.dtf_trace, &input.dtf_result) {
+        let limit = input.dtf_state.as_ref().and_then(|state| state.limit.value_in(result.unit));
+        println!("\n  {} ({})\n", "DTF".red().bold(), result.unit);
+        for line in plot::chart(trace, command.width, command.height, limit, input.dtf_marker, "m") {
+            println!("  {}", line);
+        }
+    }
+
+    if let (Some(trace), Some(result)) = (&input.rl_trace, &input.rl_result) {
+        let limit = input.rl_state.as_ref().and_then(|state| state.limit.value_in(result.unit));
+        println!("\n  {} ({})\n", "RL".blue().bold(), result.unit);
+        for line in plot::chart(trace, command.width, command.height, limit, None, "MHz") {
+            println!("  {}", line);
+        }
+    }
+}
+
+fn render(command: RenderCommand, globals: &Globals) {
+
+    let (report, _) = load_report(&command.path, globals);
+
+    for input in report.reports {
+        if command.tag.as_ref().is_some_and(|tag| *tag != input.tag) {
+            continue;
+        }
+        let rendered = render::render_report(&input, Path::new(&command.out), command.format)
+            .unwrap_or_else(|e| exit::fail(exit::Failure::write(format!("{}: {}", command.out, e))));
+        for path in rendered {
+            println!("{} {}", "Rendered:".green(), path.display());
+        }
+    }
+}
+
+fn compare(command: CompareCommand, globals: &Globals) {
+
+    let (report_a, _) = load_report(&command.path, globals);
+    let against = command.against.as_ref().map(|path| load_report(path, globals).0);
+    let report_b = against.as_ref().unwrap_or(&report_a);
+
+    let a = report_a.reports.iter().find(|report| report.tag == command.tag_a)
+        .unwrap_or_else(|| exit::fail(exit::Failure::usage(format!("No cable {} in {}", command.tag_a, command.path))));
+    let b = report_b.reports.iter().find(|report| report.tag == command.tag_b)
+        .unwrap_or_else(|| exit::fail(exit::Failure::usage(format!("No cable {} in {}", command.tag_b, command.against.as_ref().unwrap_or(&command.path)))));
+    let comparison = compare::compare(a, b);
+
+    println!("\n{} {} {}\n", a.tag.bold().underline().green(), "vs".bold(), b.tag.bold().underline().cyan());
+
+    let highlight = |delta: Option<f64>, tolerance: f64, unit: &str| -> String {
+        match delta {
+            Some(e) if e.abs() > tolerance => format!("{:+.2}{}", e, unit).red().bold().to_string(),
+            Some(e) => format!("{:+.2}{}", e, unit).green().to_string(),
+            None => "N/A".red().to_string(),
+        }
+    };
+    let value = |value: Option<f64>| -> String {
+        match value {
+            Some(e) => format!("{:.2}", e),
+            None => "N/A".to_string(),
+        }
+    };
+
+    println!("  Length (m): {: <8} vs {: <8} Delta: {}", value(comparison.length.0), value(comparison.length.1), highlight(comparison.length_delta(), command.length_tolerance, "m"));
+    println!("  VSWR:       {: <8} vs {: <8} Delta: {}", value(comparison.vswr.0), value(comparison.vswr.1), highlight(comparison.vswr_delta(), command.vswr_tolerance, ""));
+    println!("  RL (dB):    {: <8} vs {: <8} Delta: {}", value(comparison.rl.0), value(comparison.rl.1), highlight(comparison.rl_delta(), command.rl_tolerance, "dB"));
+
+    if let Some(delta) = &comparison.dtf_trace {
+        println!("\n  {}: Max difference {} VSWR at {:.2}m", "DTF".red().bold(), highlight(Some(delta.max_difference), command.vswr_tolerance, ""), delta.at);
+        for line in plot::overlay_chart(a.dtf_trace.as_ref().unwrap(), b.dtf_trace.as_ref(), command.width, command.height, None, None, "m") {
+            println!("  {}", line);
+        }
+    }
+
+    if let Some(delta) = &comparison.rl_trace {
+        println!("\n  {}: Max difference {:+.2}dB at {:.1}MHz", "RL".blue().bold(), delta.max_difference, delta.at);
+        for line in plot::overlay_chart(a.rl_trace.as_ref().unwrap(), b.rl_trace.as_ref(), command.width, command.height, None, None, "MHz") {
+            println!("  {}", line);
+        }
+    }
+}
+
+fn diff(command: DiffCommand, globals: &Globals) {
+
+    let (old, _) = load_report(&command.old, globals);
+    let (new, _) = load_report(&command.new, globals);
+
+    let tolerances = diff::Tolerances { length: command.length_tolerance, vswr: command.vswr_tolerance, rl: command.rl_tolerance };
+    let result = diff::diff(&old, &new, tolerances);
+
+    if command.json {
+        println!("{}", serde_json::to_string_pretty(&result).unwrap());
+        return;
+    }
+
+    println!("\n{}: ({}), {}: ({}), {}: ({}), {}: ({})\n",
+        "Added".green(), result.count(diff::Change::Added),
+        "Removed".red(), result.count(diff::Change::Removed),
+        "Changed".yellow(), result.count(diff::Change::Changed),
+        "Unchanged".green(), result.count(diff::Change::Unchanged),
+    );
+
+    for tag in &result.tags {
+
+        let symbol = match tag.change {
+            diff::Change::Added => "+".green().bold().to_string(),
+            diff::Change::Removed => "-".red().bold().to_string(),
+            diff::Change::Changed => "~".yellow().bold().to_string(),
+            diff::Change::Unchanged if command.all => "=".to_string(),
+            diff::Change::Unchanged => continue,
+        };
+
+        println!("{} {}: {: <15} {}: {} {}: {} {}: {} {}",
+            symbol,
+            "Tag".green(),
+            tag.tag,
+            "Length".green(),
+            format_metric_change(&tag.length, 2, "m", 18),
+            "VSWR".green(),
+            format_metric_change(&tag.vswr, 3, "", 16),
+            "RL".green(),
+            format_metric_change(&tag.rl, 2, "dB", 20),
+            match tag.transition {
+                Some(diff::Transition::Fixed) => "FAIL -> PASS".green().bold().to_string(),
+                Some(diff::Transition::Broke) => "PASS -> FAIL".red().bold().to_string(),
+                None => String::new(),
+            },
+        );
+    }
+}
+
+fn format_metric_change(change: &diff::MetricChange, precision: usize, unit: &str, width: usize) -> String {
+
+    let value = |value: Option<f64>| match value {
+        Some(e) => format!("{:.*}{}", precision, e, unit),
+        None => "N/A".to_string(),
+    };
+
+    let text = match (change.old, change.new) {
+        (Some(_), Some(new)) if change.trend == diff::Trend::Unchanged => value(Some(new)),
+        (old, new) => format!("{} -> {}", value(old), value(new)),
+    };
+
+    //pad before colouring, the escape codes would throw out the column widths
+    let text = format!("{: <width$}", text);
+    match change.trend {
+        diff::Trend::Improved => text.green().to_string(),
+        diff::Trend::Regressed => text.red().to_string(),
+        diff::Trend::Changed => text.yellow().to_string(),
+        diff::Trend::Unchanged => text,
+    }
+}
+
+fn verify(command: VerifyCommand, globals: &Globals) {
+
+    let source = Input::open(&command.path).unwrap_or_else(|e| exit::fail(exit::Failure::parse(e)));
+    let raw_report = raw_ipa_report::raw_report_from_str(remove_non_ascii(source.report_xml())).unwrap_or_else(|e| exit::fail(exit::Failure::parse(format!("{}: {}", command.path, e))));
+
+    println!("\n{}: {}\n", "Report SHA-256".green(), verify::sha256_hex(&source.report_data).yellow());
+
+    let results = verify::verify_devices(&raw_report);
+
+    for (i, result) in results.iter().enumerate() {
+        println!("{}{}{}: Model: {}, SN: {}, Digest: {}, Signature: {}",
+            "Device: (".green(), i.red(), ")".green(),
+            result.model.yellow(),
+            result.serial_number.yellow(),
+            result.digest,
+            match result.status {
+                verify::SignatureStatus::Valid => "Matches digest".green().to_string(),
+                verify::SignatureStatus::Unsigned => "Unsigned".red().bold().to_string(),
+                verify::SignatureStatus::UnknownScheme => "Not verified, unknown scheme".yellow().to_string(),
+            }
+        );
+    }
+
+    //a matching digest is easy to forge, so say what was and wasn't checked
+    println!("
+The digest is an unkeyed SHA-256 of the device block and test results, anyone can recompute it.");
+
+    if results.iter().any(|e| e.status == verify::SignatureStatus::UnknownScheme) {
+        println!("{}", "Signatures that don't match the digest weren't verified: the instrument's signing scheme isn't supported.".yellow().bold());
+    }
+
+    //an unknown scheme says nothing either way, so it is only a warning
+    exit::exit_with(&results.iter().map(|e| match e.status {
+        verify::SignatureStatus::Valid => exit::ExitCode::Ok,
+        verify::SignatureStatus::Unsigned => exit::ExitCode::CheckFailed,
+        verify::SignatureStatus::UnknownScheme if globals.strict => exit::ExitCode::Warnings,
+        verify::SignatureStatus::UnknownScheme => exit::ExitCode::Ok,
+    }).collect::<Vec<exit::ExitCode>>());
+}
+
+fn read_key(path: &str) -> Vec<u8> {
+    fs::read(path).unwrap_or_else(|e| exit::fail(exit::Failure::usage(format!("Key {}: {}", path, e))))
+}
+
+fn check_manifest(command: CheckManifestCommand) {
+
+    let key = command.key.as_ref().map(|key| read_key(key));
+    let result = manifest::check_package(Path::new(&command.path), key.as_deref())
+        .unwrap_or_else(|e| exit::fail(exit::Failure::parse(format!("{}: {}", command.path, e))));
+
+    for (name, status) in &result.entries {
+        println!("{} {}",
+            match status {
+                manifest::EntryStatus::Ok => format!("{: <8}", "OK").green().to_string(),
+                manifest::EntryStatus::Modified => "MODIFIED".red().bold().to_string(),
+                manifest::EntryStatus::Missing => format!("{: <8}", "MISSING").red().to_string(),
+                manifest::EntryStatus::Unlisted => "UNLISTED".yellow().to_string(),
+            },
+            name
+        );
+    }
+
+    println!("\n{}: {}", "Signature".green(), match result.signature {
+        manifest::SignatureCheck::Valid => "Valid".green().to_string(),
+        manifest::SignatureCheck::Invalid => "Invalid".red().bold().to_string(),
+        manifest::SignatureCheck::Missing => "Missing".red().to_string(),
+        manifest::SignatureCheck::NotChecked => "Not checked, no key given".yellow().to_string(),
+    });
+
+    exit::exit_with(&[match result.is_valid() {
+        true => exit::ExitCode::Ok,
+        false => exit::ExitCode::CheckFailed,
+    }]);
+}
+
+fn detailed_summary(unit: Option<metrics::Unit>, input: ipa_report::SweepReport){
+
+    println!("\n{}: {}", "Report Version".green(), input.version.yellow());
 
-fn detailed_summary(sort: bool, mut input: ipa_report::SweepReport){
     for i in 0..input.devices.len() {
 
         let devce = input.devices.get(i).unwrap();
         
-        println!("\n{}: Model: {}, SN: {}, Version: {}, FCal_Date: {}\n",
-            format!("{}{}{}", "Device: (".green(), i.red(), ")".green()), 
+        println!("\n{}{}{}: Model: {}, SN: {}, Version: {}, FCal_Date: {}\n",
+            "Device: (".green(), i.red(), ")".green(),
             devce.model.yellow(),
             devce.serial_number.yellow(),
             devce.sw_version.yellow(),
@@ -77,64 +1058,70 @@ fn detailed_summary(sort: bool, mut input: ipa_report::SweepReport){
         )
     }
 
-    if sort{
-        input.reports.sort_by(|a, b| a.tag.cmp(&b.tag));
-    }
-
-    // println!("{}: {: <15} {}: {: <6} {}: {: <6} {}: {: <6}",
 
     for input in input.reports {
         println!("\nTag: {}\n", input.tag.bold().underline().green());
 
-        println!("  {:<4}: Length (m): {:<16} VSWR: {:<46} || T+Cal: {} Date: {}",
-            "DTF".red().bold(),
+        //pad before colouring, the escape codes would throw out the columns
+        println!("  {}: Length (m): {} {}: {} || T+Cal: {} Date: {} {}",
+            format!("{:<4}", "DTF").red().bold(),
             match input.dtf_marker {
-                Some(e) => round::half_away_from_zero(e, 2).green().to_string(),
-                None => "N/A".red().to_string(),
+                Some(e) => format!("{:<6}", round::half_away_from_zero(e, 2)).green(),
+                None => format!("{:<6}", "N/A").red(),
             },
+            unit.map_or("VSWR", |_| "Worst"),
             match input.dtf_result.clone() {
-                Some(e) => format!("{} at {}{}",round::half_away_from_zero(e.max.1, 2).green(), round::half_away_from_zero(e.max.0, 2).green(), "m".green()),
-                None => "N/A".red().to_string(),
+                Some(e) => format!("{:<22}", format!("{} at {}m", table::format_result(&e, unit), round::half_away_from_zero(e.max.0, 2))).green(),
+                None => format!("{:<22}", "N/A").red(),
             },
             match input.dtf_result.clone() {
-                Some(e) => format!("{:0>2}:{:0>2}", e.calibrated.hour(), e.calibrated.minute()).green().to_string(),
+                Some(e) => format_elapsed(e.calibration_age()).green().to_string(),
                 None => "N/A".red().to_string(),
             },
             match input.dtf_result.clone() {
-                Some(e) => format!("{} {:0>2}:{:0>2}", e.time.date(), e.time.hour(), e.time. minute()).green().to_string().to_string(),
+                Some(e) => e.time.format("%Y-%m-%d %H:%M %Z").green().to_string(),
                 None => "N/A".red().to_string(),
+            },
+            match &input.dtf_trace {
+                Some(e) => plot::sparkline(e, 24).red().to_string(),
+                None => String::new(),
             }
 
             
         );
 
-        println!("  {:<4}: RL (dBm):  {:<41}|| T+Cal: {} Date: {}",
-            input.rl_state.unwrap().test_type.blue().bold(),
+        println!("  {}: {}:  {}|| T+Cal: {} Date: {} {}",
+            format!("{:<4}", input.rl_state.map_or("RL".to_string(), |e| e.test_type)).blue().bold(),
+            unit.map_or("RL (dBm)", |_| "Worst   "),
             match input.rl_result.clone() {
-                Some(e) => round::half_away_from_zero(e.max.1, 2).green().to_string(),
-                None => "N/A".red().to_string(),
+                Some(e) => format!("{:<37}", table::format_result(&e, unit)).green(),
+                None => format!("{:<37}", "N/A").red(),
             },
             match input.rl_result.clone() {
-                Some(e) => format!("{:0>2}:{:0>2}", e.calibrated.hour(), e.calibrated.minute()).green().to_string(),
+                Some(e) => format_elapsed(e.calibration_age()).green().to_string(),
                 None => "N/A".red().to_string(),
             },
             match input.rl_result.clone() {
-                Some(e) => format!("{} {:0>2}:{:0>2}", e.time.date(), e.time.hour(), e.time. minute()).green().to_string().to_string(),
+                Some(e) => e.time.format("%Y-%m-%d %H:%M %Z").green().to_string(),
                 None => "N/A".red().to_string(),
+            },
+            match &input.rl_trace {
+                Some(e) => plot::sparkline(e, 24).blue().to_string(),
+                None => String::new(),
             }
         );
 
     }
 }
 
-fn summary(sort: bool,mut input: ipa_report::SweepReport){
+fn summary(unit: Option<metrics::Unit>, columns: &[table::Column], input: ipa_report::SweepReport){
 
     for i in 0..input.devices.len() {
 
         let devce = input.devices.get(i).unwrap();
         
-        println!("\n{}: Model: {}, SN: {}, Version: {}, FCal_Date: {}\n",
-            format!("{}{}{}", "Device: (".green(), i.red(), ")".green()), 
+        println!("\n{}{}{}: Model: {}, SN: {}, Version: {}, FCal_Date: {}\n",
+            "Device: (".green(), i.red(), ")".green(),
             devce.model.yellow(),
             devce.serial_number.yellow(),
             devce.sw_version.yellow(),
@@ -142,32 +1129,13 @@ fn summary(sort: bool,mut input: ipa_report::SweepReport){
         )
     }
 
-    if sort{
-        input.reports.sort_by(|a, b| a.tag.cmp(&b.tag));
+    for line in table::Table::from_sweep_report(&input, columns, unit).render(table::Style::Plain) {
+        println!("{}", line);
     }
-    
-    for input in input.reports{
+}
 
-        println!("{}: {: <15} {}: {: <6} {}: {: <6} {}: {: <6}",
-        "Tag".green(),
-        input.tag,
-        "Length".green(),
-        match input.dtf_marker {
-            Some(e) => round::half_away_from_zero(e, 2).to_string(),
-            None => "N/A".red().to_string(),
-        },
-        "VSWR".green(),
-        match input.dtf_result {
-            Some(e) => round::half_away_from_zero(e.max.1, 2).to_string(),
-            None => "N/A".red().to_string(),
-        },
-        "RL".green(),
-        match input.rl_result {
-            Some(e) => round::half_away_from_zero(e.max.1, 2).to_string(),
-            None => "N/A".red().to_string(),
-        },
-        );
-    }
+fn format_elapsed(elapsed: Duration) -> String {
+    format!("{:0>2}:{:0>2}", elapsed.num_hours(), elapsed.num_minutes() % 60)
 }
 
 fn remove_non_ascii(input: String) -> String {
@@ -0,0 +1,201 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{input::{relative_path, Input, REPORT_NAME}, verify::sha256_hex};
+
+pub const MANIFEST_NAME: &str = "MANIFEST.sha256";
+pub const SIGNATURE_NAME: &str = "MANIFEST.sha256.sig";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntryStatus {
+    Ok,
+    Modified,
+    Missing,
+    ///in the package but not listed in the manifest
+    Unlisted,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignatureCheck {
+    Valid,
+    Invalid,
+    Missing,
+    ///no key was given, so the signature was not checked
+    NotChecked,
+}
+
+pub struct ManifestCheck {
+    pub entries: Vec<(String, EntryStatus)>,
+    pub signature: SignatureCheck,
+}
+
+impl ManifestCheck {
+    pub fn is_valid(&self) -> bool {
+        self.entries.iter().all(|(_, status)| *status == EntryStatus::Ok)
+            && matches!(self.signature, SignatureCheck::Valid | SignatureCheck::NotChecked)
+    }
+}
+
+///`path` under `dir` as a manifest name, `/` separated.
+fn package_name(dir: &Path, path: &Path) -> Result<String, String> {
+    let relative = path.strip_prefix(dir).map_err(|_| format!("{} is not inside {}", path.display(), dir.display()))?;
+    Ok(relative.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/"))
+}
+
+///Turns an export directory into a deliverable package: copies Report.xml and its assets in next to
+///the generated files, then writes MANIFEST.sha256 (`sha256sum -c` compatible) and, given a key,
+///MANIFEST.sha256.sig holding the hex HMAC-SHA256 of the manifest. `generated` must be inside `dir`.
+///An asset named like a generated file or the manifest is refused rather than written over it.
+pub fn write_package(dir: &Path, source: &Input, assets: &[String], generated: &[PathBuf], key: Option<&[u8]>) -> Result<PathBuf, String> {
+
+    let generated: Vec<String> = generated.iter().map(|path| package_name(dir, path)).collect::<Result<_, _>>()?;
+    let reserved: Vec<&str> = generated.iter().map(|e| e.as_str()).chain([REPORT_NAME, MANIFEST_NAME, SIGNATURE_NAME]).collect();
+
+    //file systems that ignore case would still overwrite
+    if let Some(asset) = assets.iter().find(|asset| reserved.iter().any(|name| name.eq_ignore_ascii_case(asset))) {
+        return Err(format!("Asset {} has the name of a file the package already holds", asset));
+    }
+
+    let mut files: Vec<String> = Vec::new();
+
+    fs::write(dir.join(REPORT_NAME), &source.report_data).map_err(|e| e.to_string())?;
+    files.push(REPORT_NAME.to_string());
+
+    for asset in assets {
+        //missing thumbnails are common, they just don't get listed
+        if source.has_asset(asset) {
+            let path = relative_path(dir, asset)?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            fs::write(&path, source.read_asset(asset)?).map_err(|e| e.to_string())?;
+            files.push(asset.clone());
+        }
+    }
+
+    for name in generated {
+        if !files.contains(&name) {
+            files.push(name);
+        }
+    }
+
+    let mut manifest = String::new();
+    for name in &files {
+        let data = fs::read(dir.join(name)).map_err(|e| e.to_string())?;
+        manifest.push_str(&format!("{}  {}\n", sha256_hex(&data), name));
+    }
+
+    let manifest_path = dir.join(MANIFEST_NAME);
+    fs::write(&manifest_path, &manifest).map_err(|e| e.to_string())?;
+
+    if let Some(key) = key {
+        fs::write(dir.join(SIGNATURE_NAME), format!("{}\n", sign(key, manifest.as_bytes()))).map_err(|e| e.to_string())?;
+    }
+
+    Ok(manifest_path)
+}
+
+pub fn check_package(dir: &Path, key: Option<&[u8]>) -> Result<ManifestCheck, String> {
+
+    let manifest = fs::read_to_string(dir.join(MANIFEST_NAME)).map_err(|e| format!("{}: {}", MANIFEST_NAME, e))?;
+    let mut entries: Vec<(String, EntryStatus)> = Vec::new();
+
+    for line in manifest.lines().filter(|line| !line.trim().is_empty()) {
+        let (digest, name) = line.split_once("  ").ok_or(format!("Malformed manifest line: {}", line))?;
+
+        let status = match fs::read(relative_path(dir, name)?) {
+            Ok(data) if sha256_hex(&data) == digest => EntryStatus::Ok,
+            Ok(_) => EntryStatus::Modified,
+            Err(_) => EntryStatus::Missing,
+        };
+        entries.push((name.to_string(), status));
+    }
+
+    let mut files: Vec<PathBuf> = Vec::new();
+    list_files(dir, &mut files)?;
+
+    for file in files {
+        let name = package_name(dir, &file)?;
+        if name != MANIFEST_NAME && name != SIGNATURE_NAME && !entries.iter().any(|(e, _)| *e == name) {
+            entries.push((name, EntryStatus::Unlisted));
+        }
+    }
+
+    let signature = match (key, fs::read_to_string(dir.join(SIGNATURE_NAME))) {
+        (None, _) => SignatureCheck::NotChecked,
+        (Some(_), Err(_)) => SignatureCheck::Missing,
+        (Some(key), Ok(signature)) => match verify_signature(key, manifest.as_bytes(), signature.trim()) {
+            true => SignatureCheck::Valid,
+            false => SignatureCheck::Invalid,
+        },
+    };
+
+    Ok(ManifestCheck { entries, signature })
+}
+
+///Every file under `dir`, in subdirectories too.
+fn list_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir).map_err(|e| e.to_string())?
+        .map(|entry| entry.map(|e| e.path()).map_err(|e| e.to_string()))
+        .collect::<Result<_, _>>()?;
+    entries.sort();
+
+    for path in entries {
+        match path.is_dir() {
+            true => list_files(&path, files)?,
+            false => files.push(path),
+        }
+    }
+    Ok(())
+}
+
+fn mac(key: &[u8], data: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac
+}
+
+fn sign(key: &[u8], data: &[u8]) -> String {
+    mac(key, data).finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+///Compares in constant time, so the signature can't be guessed a byte at a time.
+fn verify_signature(key: &[u8], data: &[u8], signature: &str) -> bool {
+    let bytes: Option<Vec<u8>> = match signature.len() % 2 {
+        0 => (0..signature.len()).step_by(2).map(|i| u8::from_str_radix(signature.get(i..i + 2)?, 16).ok()).collect(),
+        _ => None,
+    };
+    bytes.is_some_and(|bytes| mac(key, data).verify_slice(&bytes).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_are_checked_against_the_key() {
+        let signature = sign(b"key", b"manifest");
+
+        assert!(verify_signature(b"key", b"manifest", &signature));
+        assert!(verify_signature(b"key", b"manifest", &signature.to_uppercase()));
+        assert!(!verify_signature(b"other", b"manifest", &signature));
+        assert!(!verify_signature(b"key", b"manifest", &signature[1..]));
+        assert!(!verify_signature(b"key", b"manifest", "zz"));
+    }
+
+    #[test]
+    fn assets_named_like_generated_files_are_refused() {
+        let dir = std::env::temp_dir().join(format!("kaelus_package_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = Input::open("fixtures/v1.0").unwrap();
+
+        let clash = write_package(&dir, &source, &["REPORT.JSON".to_string()], &[dir.join("report.json")], None);
+        let fine = write_package(&dir, &source, &["Test_001_DTF.csv".to_string()], &[], None);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(clash.unwrap_err().contains("REPORT.JSON"));
+        assert!(fine.is_ok());
+    }
+}
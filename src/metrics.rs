@@ -0,0 +1,144 @@
+use std::{fmt, str::FromStr};
+
+///Units a reflection measurement can be shown in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    Vswr,
+    ///return loss in dB, negative like the tester's RL results
+    ReturnLoss,
+    ///magnitude of the reflection coefficient, 0 to 1
+    Reflection,
+    ///mismatch loss in dB
+    MismatchLoss,
+}
+
+impl FromStr for Unit {
+    type Err = String;
+
+    ///Accepts the units used in Report.xml ("VSWR", "dB") as well as the names used on the command line.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.trim().to_ascii_lowercase().as_str() {
+            "vswr" => Ok(Unit::Vswr),
+            "db" | "rl" | "return-loss" => Ok(Unit::ReturnLoss),
+            "rho" | "gamma" | "reflection" => Ok(Unit::Reflection),
+            "ml" | "mismatch" | "mismatch-loss" => Ok(Unit::MismatchLoss),
+            _ => Err(format!("Unknown unit: {}", input)),
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Unit::Vswr => write!(f, "VSWR"),
+            Unit::ReturnLoss => write!(f, "dB RL"),
+            Unit::Reflection => write!(f, "rho"),
+            Unit::MismatchLoss => write!(f, "dB ML"),
+        }
+    }
+}
+
+pub fn vswr_to_reflection(vswr: f64) -> f64 {
+    if vswr.is_infinite() {
+        return 1.0;
+    }
+    (vswr - 1.0) / (vswr + 1.0)
+}
+
+pub fn reflection_to_vswr(reflection: f64) -> f64 {
+    if reflection >= 1.0 {
+        return f64::INFINITY;
+    }
+    (1.0 + reflection) / (1.0 - reflection)
+}
+
+///Takes return loss of either sign, the tester reports it as negative dB.
+pub fn return_loss_to_reflection(return_loss: f64) -> f64 {
+    10f64.powf(-return_loss.abs() / 20.0)
+}
+
+pub fn reflection_to_return_loss(reflection: f64) -> f64 {
+    20.0 * reflection.log10()
+}
+
+pub fn mismatch_loss_to_reflection(mismatch_loss: f64) -> f64 {
+    (1.0 - 10f64.powf(-mismatch_loss.abs() / 10.0)).sqrt()
+}
+
+pub fn reflection_to_mismatch_loss(reflection: f64) -> f64 {
+    -10.0 * (1.0 - reflection * reflection).log10()
+}
+
+pub fn vswr_to_return_loss(vswr: f64) -> f64 {
+    reflection_to_return_loss(vswr_to_reflection(vswr))
+}
+
+pub fn convert(value: f64, from: Unit, to: Unit) -> f64 {
+
+    let reflection = match from {
+        Unit::Vswr => vswr_to_reflection(value),
+        Unit::ReturnLoss => return_loss_to_reflection(value),
+        Unit::Reflection => value,
+        Unit::MismatchLoss => mismatch_loss_to_reflection(value),
+    };
+
+    match to {
+        Unit::Vswr => reflection_to_vswr(reflection),
+        Unit::ReturnLoss => reflection_to_return_loss(reflection),
+        Unit::Reflection => reflection,
+        Unit::MismatchLoss => reflection_to_mismatch_loss(reflection),
+    }
+}
+
+///Formats a value in `unit` with the precision that unit is normally read at. A perfect match has
+///infinite return loss and total reflection an infinite VSWR, those are named instead of printed as inf.
+pub fn format_value(value: f64, unit: Unit) -> String {
+    match convert(value, unit, Unit::Reflection) {
+        reflection if reflection <= 0.0 => return "Perfect match".to_string(),
+        reflection if reflection >= 1.0 => return "Total reflection".to_string(),
+        _ => {},
+    }
+    match unit {
+        Unit::Vswr => format!("{:.2}", value),
+        Unit::ReturnLoss => format!("{:.2}", value),
+        Unit::Reflection => format!("{:.3}", value),
+        Unit::MismatchLoss => format!("{:.3}", value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNITS: [Unit; 4] = [Unit::Vswr, Unit::ReturnLoss, Unit::Reflection, Unit::MismatchLoss];
+
+    #[test]
+    fn converts_a_known_vswr() {
+        assert!((convert(1.5, Unit::Vswr, Unit::Reflection) - 0.2).abs() < 1e-12);
+        assert!((convert(1.5, Unit::Vswr, Unit::ReturnLoss) + 13.979).abs() < 1e-3);
+        assert!((convert(1.5, Unit::Vswr, Unit::MismatchLoss) - 0.177).abs() < 1e-3);
+        //the tester's RL results are negative, a positive return loss means the same match
+        assert_eq!(convert(-13.979, Unit::ReturnLoss, Unit::Vswr), convert(13.979, Unit::ReturnLoss, Unit::Vswr));
+    }
+
+    #[test]
+    fn every_unit_converts_back_to_where_it_started() {
+        for vswr in [1.01, 1.2, 1.5, 3.0, 10.0] {
+            for unit in UNITS {
+                let there = convert(vswr, Unit::Vswr, unit);
+                assert!((convert(there, unit, Unit::Vswr) - vswr).abs() < 1e-9, "{} via {}", vswr, unit);
+            }
+        }
+    }
+
+    #[test]
+    fn a_perfect_match_is_named_rather_than_infinite() {
+        assert_eq!(convert(1.0, Unit::Vswr, Unit::ReturnLoss), f64::NEG_INFINITY);
+        for unit in UNITS {
+            assert_eq!(format_value(convert(1.0, Unit::Vswr, unit), unit), "Perfect match");
+        }
+        assert_eq!(format_value(f64::INFINITY, Unit::Vswr), "Total reflection");
+        assert_eq!(format_value(1.5, Unit::Vswr), "1.50");
+        assert_eq!(format_value(-13.979, Unit::ReturnLoss), "-13.98");
+    }
+}
@@ -0,0 +1,72 @@
+use std::{cell::Cell, panic, sync::atomic::{AtomicUsize, Ordering}, thread};
+
+//0 until --jobs is given, which uses every core
+static THREADS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    //set on worker threads, so a map inside a map (traces inside a batch) runs in place
+    static WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+pub fn set_threads(threads: usize) {
+    THREADS.store(threads, Ordering::Relaxed);
+}
+
+///Threads `map` uses: `--jobs`, or one per core.
+pub fn threads() -> usize {
+    match THREADS.load(Ordering::Relaxed) {
+        0 => thread::available_parallelism().map_or(1, |e| e.get()),
+        threads => threads,
+    }
+}
+
+///Applies `f` to every item across `threads()` threads. Results come back in the order of `items`,
+///whichever thread finished first.
+pub fn map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    map_on(threads(), items, f)
+}
+
+///As `map` on at most `threads` threads.
+fn map_on<T: Sync, R: Send>(threads: usize, items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+
+    let threads = threads.min(items.len());
+    if threads <= 1 || WORKER.get() {
+        return items.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let f = &f;
+    let next = &next;
+
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads).map(|_| scope.spawn(move || {
+            WORKER.set(true);
+            //hand out items one at a time so one slow export doesn't hold up a whole chunk
+            let mut done: Vec<(usize, R)> = Vec::new();
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                match items.get(i) {
+                    Some(item) => done.push((i, f(item))),
+                    None => break done,
+                }
+            }
+        })).collect();
+
+        //a panicking `f` is passed on as it was, not as a generic join error
+        workers.into_iter().flat_map(|worker| worker.join().unwrap_or_else(|e| panic::resume_unwind(e))).collect()
+    });
+
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_panic_on_a_worker_keeps_its_message() {
+        let payload = panic::catch_unwind(|| map_on(2, &[1, 2, 3], |i| if *i == 2 { panic!("item 2") } else { *i })).unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"item 2"));
+    }
+}
@@ -0,0 +1,196 @@
+use crate::color::Colorize;
+
+use crate::trace::Trace;
+
+const SPARK_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+//dot bit for each (column, row) inside a braille cell
+const BRAILLE_DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+
+///Peak of each of `width` equal slices of the trace, so narrow reflections don't vanish when squeezed.
+fn resample(trace: &Trace, width: usize) -> Vec<f64> {
+
+    let points = &trace.points;
+    let mut values: Vec<f64> = Vec::new();
+
+    if points.is_empty() || width == 0 {
+        return values;
+    }
+
+    for i in 0..width {
+        let start = i * points.len() / width;
+        let end = ((i + 1) * points.len() / width).max(start + 1).min(points.len());
+        values.push(points[start..end].iter().map(|point| point.1).fold(f64::MIN, f64::max));
+    }
+
+    values
+}
+
+pub fn sparkline(trace: &Trace, width: usize) -> String {
+
+    let values = resample(trace, width);
+    let min = values.iter().cloned().fold(f64::MAX, f64::min);
+    let max = values.iter().cloned().fold(f64::MIN, f64::max);
+
+    values.iter().map(|value| {
+        let level = match max > min {
+            true => ((value - min) / (max - min) * (SPARK_LEVELS.len() - 1) as f64).round() as usize,
+            false => 0,
+        };
+        SPARK_LEVELS[level]
+    }).collect()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Layer {
+    Empty,
+    Trace,
+    ///the second trace of an overlay
+    Overlay,
+    Limit,
+    Marker,
+}
+
+///Renders a trace as a braille chart `width` x `height` characters, with the limit as a horizontal line
+///and the marker as a vertical one. Returns the lines ready to print, y axis labels included.
+pub fn chart(trace: &Trace, width: usize, height: usize, limit: Option<f64>, marker: Option<f64>, x_unit: &str) -> Vec<String> {
+    overlay_chart(trace, None, width, height, limit, marker, x_unit)
+}
+
+///Like `chart`, with a second trace drawn in cyan over the same axes.
+pub fn overlay_chart(trace: &Trace, overlay: Option<&Trace>, width: usize, height: usize, limit: Option<f64>, marker: Option<f64>, x_unit: &str) -> Vec<String> {
+
+    let traces: Vec<(&Trace, Layer)> = match overlay {
+        Some(overlay) => vec![(overlay, Layer::Overlay), (trace, Layer::Trace)],
+        None => vec![(trace, Layer::Trace)],
+    };
+    let points: Vec<(f64, f64)> = traces.iter().flat_map(|(trace, _)| trace.points.iter().cloned()).collect();
+    let mut lines: Vec<String> = Vec::new();
+
+    if points.is_empty() || width == 0 || height == 0 {
+        return lines;
+    }
+
+    let x_min = points.iter().map(|point| point.0).fold(f64::MAX, f64::min);
+    let x_max = points.iter().map(|point| point.0).fold(f64::MIN, f64::max);
+    let mut y_min = points.iter().map(|point| point.1).fold(f64::MAX, f64::min);
+    let mut y_max = points.iter().map(|point| point.1).fold(f64::MIN, f64::max);
+
+    if let Some(limit) = limit {
+        y_min = y_min.min(limit);
+        y_max = y_max.max(limit);
+    }
+    let padding = ((y_max - y_min) * 0.05).max(0.001);
+    y_min -= padding;
+    y_max += padding;
+
+    let columns = width * 2;
+    let rows = height * 4;
+    let mut dots: Vec<Vec<Layer>> = vec![vec![Layer::Empty; columns]; rows];
+
+    let to_row = |value: f64| -> usize {
+        let row = ((y_max - value) / (y_max - y_min) * (rows - 1) as f64).round();
+        row.clamp(0.0, (rows - 1) as f64) as usize
+    };
+
+    if let Some(limit) = limit {
+        let row = to_row(limit);
+        for column in (0..columns).step_by(2) {
+            dots[row][column] = Layer::Limit;
+        }
+    }
+
+    if let Some(marker) = marker.filter(|marker| *marker >= x_min && *marker <= x_max && x_max > x_min) {
+        let column = ((marker - x_min) / (x_max - x_min) * (columns - 1) as f64).round() as usize;
+        for row in dots.iter_mut().step_by(2) {
+            row[column] = Layer::Marker;
+        }
+    }
+
+    //joins each column to the previous one so steep edges stay continuous
+    for (trace, layer) in &traces {
+        //one of the two traces can be empty when the other isn't
+        let (Some(first), Some(last)) = (trace.points.first().map(|e| e.0), trace.points.last().map(|e| e.0)) else {
+            continue;
+        };
+        let start = ((first - x_min) / (x_max - x_min).max(f64::EPSILON) * (columns - 1) as f64).round() as usize;
+        let end = ((last - x_min) / (x_max - x_min).max(f64::EPSILON) * (columns - 1) as f64).round() as usize;
+
+        let values = resample(trace, end.saturating_sub(start) + 1);
+        let mut previous: Option<usize> = None;
+        for (i, value) in values.iter().enumerate() {
+            let row = to_row(*value);
+            let (top, bottom) = match previous {
+                Some(previous) => (row.min(previous), row.max(previous)),
+                None => (row, row),
+            };
+            for dot_row in dots.iter_mut().take(bottom + 1).skip(top) {
+                dot_row[start + i] = *layer;
+            }
+            previous = Some(row);
+        }
+    }
+
+    let label_width = format!("{:.2}", y_max).len().max(format!("{:.2}", y_min).len());
+
+    for cell_row in 0..height {
+
+        let label = match cell_row {
+            0 => format!("{:.2}", y_max),
+            _ if cell_row == height - 1 => format!("{:.2}", y_min),
+            _ => String::new(),
+        };
+        let mut line = format!("{: >width$} ┤", label, width = label_width);
+
+        for cell_column in 0..width {
+            let mut bits: u32 = 0;
+            let mut layer = Layer::Empty;
+
+            for (dx, column_dots) in BRAILLE_DOTS.iter().enumerate() {
+                for (dy, bit) in column_dots.iter().enumerate() {
+                    let dot = dots[cell_row * 4 + dy][cell_column * 2 + dx];
+                    if dot != Layer::Empty {
+                        bits |= bit;
+                        //the traces win over the limit and marker when they share a cell
+                        if layer != Layer::Trace && layer != Layer::Overlay {
+                            layer = dot;
+                        }
+                    }
+                }
+            }
+
+            let cell = char::from_u32(0x2800 + bits).unwrap().to_string();
+            line.push_str(&match layer {
+                Layer::Trace => cell.green().to_string(),
+                Layer::Overlay => cell.cyan().to_string(),
+                Layer::Limit => cell.red().to_string(),
+                Layer::Marker => cell.yellow().to_string(),
+                Layer::Empty => cell,
+            });
+        }
+        lines.push(line);
+    }
+
+    let x_start = format!("{:.1}", x_min);
+    let x_end = format!("{:.1}{}", x_max, x_unit);
+    lines.push(format!("{: >width$} └{}", "", "─".repeat(width), width = label_width));
+    lines.push(format!("{: >width$}  {}{: >end_width$}", "", x_start, x_end,
+        width = label_width,
+        end_width = width.saturating_sub(x_start.len())));
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlays_an_empty_trace() {
+        let trace = Trace { points: vec![(0.0, 1.1), (1.0, 1.3), (2.0, 1.2)] };
+        let empty = Trace { points: Vec::new() };
+
+        assert_eq!(overlay_chart(&trace, Some(&empty), 20, 4, Some(1.5), None, "m").len(), 6);
+        assert_eq!(overlay_chart(&empty, Some(&trace), 20, 4, None, None, "m").len(), 6);
+    }
+}
@@ -0,0 +1,125 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use chrono::{DateTime, Utc};
+use serde_derive::Serialize;
+
+use crate::{export::{escape, format_value}, schedule::{Reconciliation, Status}};
+
+///How one export in the drop folder was processed.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportStatus {
+    pub path: String,
+    pub cables: usize,
+    pub error: Option<String>,
+}
+
+///Cumulative progress of a site against its cable schedule, over every export seen so far.
+#[derive(Debug, Clone, Serialize)]
+pub struct Progress {
+    pub updated: String,
+    pub scheduled: usize,
+    pub complete: usize,
+    pub failed: usize,
+    pub length_mismatch: usize,
+    pub missing: usize,
+    pub unexpected: usize,
+    pub exports: Vec<ExportStatus>,
+    pub cables: Vec<Reconciliation>,
+}
+
+impl Progress {
+    pub fn new(cables: Vec<Reconciliation>, exports: Vec<ExportStatus>, updated: DateTime<Utc>) -> Progress {
+
+        let count = |status: Status| cables.iter().filter(|e| e.status == status).count();
+
+        Progress { 
+            updated: updated.format("%Y-%m-%d %H:%M:%S UTC").to_string(), 
+            scheduled: cables.iter().filter(|e| e.status != Status::Unexpected).count(), 
+            complete: count(Status::Complete), 
+            failed: count(Status::Failed), 
+            length_mismatch: count(Status::LengthMismatch), 
+            missing: count(Status::Missing), 
+            unexpected: count(Status::Unexpected), 
+            exports, 
+            cables,
+        }
+    }
+
+    pub fn percent_complete(&self) -> f64 {
+        match self.scheduled {
+            0 => 100.0,
+            scheduled => self.complete as f64 * 100.0 / scheduled as f64,
+        }
+    }
+}
+
+///Writes progress.json and progress.html into `dir`, returning the paths written.
+pub fn write_progress(progress: &Progress, dir: &Path) -> Result<Vec<PathBuf>, String> {
+
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+    let json_path = dir.join("progress.json");
+    let json = serde_json::to_string_pretty(progress).map_err(|e| e.to_string())?;
+    replace(&json_path, json.as_bytes())?;
+
+    let html_path = dir.join("progress.html");
+    replace(&html_path, progress_html(progress).as_bytes())?;
+
+    Ok(vec![json_path, html_path])
+}
+
+///Writes next to `path` and renames over it, so the page refreshing mid-write never reads half a file.
+fn replace(path: &Path, contents: &[u8]) -> Result<(), String> {
+
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+
+    fs::write(&temp, contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+    fs::rename(&temp, path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn progress_html(progress: &Progress) -> String {
+
+    let mut html = String::new();
+
+    //reloads itself, so it can be left open on the PM's screen
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<meta http-equiv=\"refresh\" content=\"30\">\n<title>Sweep Progress</title>\n");
+    html.push_str("<style>body{font-family:sans-serif}table{border-collapse:collapse}td,th{border:1px solid #999;padding:2px 8px}.fail{color:#b00}.warn{color:#a60}.ok{color:#070}progress{width:30em}</style>\n");
+    html.push_str("</head>\n<body>\n<h1>Sweep Progress</h1>\n");
+
+    html.push_str(&format!("<p><progress max=\"100\" value=\"{:.0}\"></progress> {} of {} scheduled cables complete ({:.0}%)</p>\n",
+        progress.percent_complete(), progress.complete, progress.scheduled, progress.percent_complete()));
+    html.push_str(&format!("<p>Failed: {}, Wrong length: {}, Missing: {}, Unexpected: {}</p>\n<p>Updated {}</p>\n",
+        progress.failed, progress.length_mismatch, progress.missing, progress.unexpected, progress.updated));
+
+    html.push_str("<h2>Cables</h2>\n<table>\n<tr><th>Tag</th><th>Status</th><th>Expected (m)</th><th>Measured (m)</th></tr>\n");
+    for cable in &progress.cables {
+        let (class, status) = match cable.status {
+            Status::Complete => ("ok", "Complete"),
+            Status::Failed => ("fail", "Failed"),
+            Status::LengthMismatch => ("warn", "Wrong length"),
+            Status::Missing => ("", "Missing"),
+            Status::Unexpected => ("warn", "Unexpected"),
+        };
+        html.push_str(&format!("<tr><td>{}</td><td class=\"{}\">{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape(&cable.tag),
+            class,
+            status,
+            format_value(cable.expected_length),
+            format_value(cable.measured_length),
+        ));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Exports</h2>\n<table>\n<tr><th>Export</th><th>Cables</th><th>Error</th></tr>\n");
+    for export in &progress.exports {
+        html.push_str(&format!("<tr><td>{}</td><td>{}</td><td class=\"fail\">{}</td></tr>\n",
+            escape(&export.path),
+            export.cables,
+            escape(export.error.as_deref().unwrap_or("")),
+        ));
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+
+    html
+}
@@ -1,49 +1,162 @@
-#![allow(non_snake_case)]
 
+use std::{collections::BTreeMap, str::FromStr};
+
+use chrono::{NaiveDateTime, NaiveTime};
 use serde_derive::{Deserialize, Serialize};
 use serde_xml_rs::from_str;
 
+///Bundle versions the parser has been checked against, each has a sample export under fixtures/.
+///Other versions are parsed the same way, with a warning.
+pub const TESTED_VERSIONS: [&str; 1] = ["1.0"];
+
+///Parses `Test.Time`, the wall clock time of the test.
+pub fn parse_time(input: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(input.trim(), "%Y-%m-%d %H:%M").map_err(|_| format!("Invalid time format: {}", input))
+}
+
+///Parses `Test.Calibrated`, the wall clock time of the field calibration without a date.
+pub fn parse_time_of_day(input: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(input.trim(), "%H:%M").map_err(|_| format!("Invalid calibration time: {}", input))
+}
+
+///Parses the `a:b` pairs of `Rx_kHz`, `Maximum` and `Minimum`.
+pub fn parse_pair<T: FromStr>(input: &str) -> Result<(T, T), String> {
+    input.split_once(':')
+        .and_then(|(first, second)| Some((first.trim().parse().ok()?, second.trim().parse().ok()?)))
+        .ok_or(format!("Invalid value pair: {}", input))
+}
+
 pub fn raw_report_from_str(input: String) -> Result<Bundle, serde_xml_rs::Error> {
     from_str(&input)
 }
 
+///Unrecognised child elements and attributes, keyed by name.
+pub type Extensions = BTreeMap<String, Extension>;
+
+///An element or attribute that is not part of the known schema, kept as-is.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Extension {
+    Text(String),
+    Node(BTreeMap<String, Extension>),
+}
+
+impl Bundle {
+
+    pub fn is_tested_version(&self) -> bool {
+        TESTED_VERSIONS.contains(&self.Version.trim())
+    }
+
+    ///Every asset file referenced by a test, in report order.
+    pub fn assets(&self) -> Vec<String> {
+        let mut assets: Vec<String> = Vec::new();
+
+        for report in &self.Reports.Report {
+            for test in &report.Items.Test {
+                for asset in &test.Assets.Asset {
+                    if !assets.contains(asset) {
+                        assets.push(asset.clone());
+                    }
+                }
+            }
+        }
+        assets
+    }
+
+    ///Paths of every unrecognised element or attribute in the bundle, e.g. "Devices/Device/Foo".
+    pub fn unrecognised_elements(&self) -> Vec<String> {
+        let mut paths: Vec<String> = Vec::new();
+
+        collect_extensions(&mut paths, "", &self.Extensions);
+        collect_extensions(&mut paths, "Devices/", &self.Devices.Extensions);
+
+        for device in &self.Devices.Device {
+            collect_extensions(&mut paths, "Devices/Device/", &device.Extensions);
+            for details in &device.Details.DeviceDetails {
+                collect_extensions(&mut paths, "Devices/Device/Details/DeviceDetails/", &details.Extensions);
+            }
+        }
+
+        for state in &self.States.State {
+            collect_extensions(&mut paths, "States/State/", &state.Extensions);
+            for limit in &state.Limits.Limit {
+                collect_extensions(&mut paths, "States/State/Limits/Limit/", &limit.Extensions);
+            }
+        }
+
+        for report in &self.Reports.Report {
+            collect_extensions(&mut paths, "Reports/Report/", &report.Extensions);
+            for test in &report.Items.Test {
+                collect_extensions(&mut paths, "Reports/Report/Items/Test/", &test.Extensions);
+                collect_extensions(&mut paths, "Reports/Report/Items/Test/Results/TestResult/", &test.Results.TestResult.Extensions);
+            }
+        }
+
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+}
+
+fn collect_extensions(paths: &mut Vec<String>, prefix: &str, extensions: &Extensions) {
+    for key in extensions.keys() {
+        paths.push(format!("{}{}", prefix, key));
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[allow(non_snake_case)]
 pub struct Bundle {
     pub Version: String,
     pub Devices: Devices,
     pub States: States,
-    pub Reports: Reports
+    pub Reports: Reports,
+    #[serde(flatten)]
+    pub Extensions: Extensions
 }
     #[derive(Debug, Serialize, Deserialize, Clone)]
+    #[allow(non_snake_case)]
     pub struct Devices {
         pub Device: Vec<Device>,
+        #[serde(flatten)]
+        pub Extensions: Extensions
     }
         #[derive(Debug, Serialize, Deserialize, Clone)]
+        #[allow(non_snake_case)]
         pub struct Device {
             pub SerialNumber: String,
             pub Model: String,
-            pub Details: Details
+            pub Details: Details,
+            #[serde(flatten)]
+            pub Extensions: Extensions
         }
         
             #[derive(Debug, Serialize, Deserialize, Clone)]
+            #[allow(non_snake_case)]
             pub struct Details {
                 pub DeviceDetails: Vec<DeviceDetails>
             }
                 
                 #[derive(Debug, Serialize, Deserialize, Clone)]
+                #[allow(non_snake_case)]
                 pub struct DeviceDetails {
                     pub ID: String,
                     pub SWVersions: String,
                     pub CalDate: String,
-                    pub Signature: String,
+                    //not present on every firmware
+                    pub Signature: Option<String>,
+                    #[serde(flatten)]
+                    pub Extensions: Extensions
                 }
     
     #[derive(Debug, Serialize, Deserialize, Clone)]
+    #[allow(non_snake_case)]
     pub struct States {
         pub State: Vec<State>
     }
 
         #[derive(Debug, Serialize, Deserialize, Clone)] 
+        #[allow(non_snake_case)]
         pub struct State {
             pub ID: String,
             pub TestType: String,
@@ -53,81 +166,107 @@ pub struct Bundle {
             pub VF: Option<String>,
             pub Window: Option<String>,
             pub CableLoss_dB_per_m: Option<String>,
-            pub Limits: Limits
+            pub Limits: Limits,
+            #[serde(flatten)]
+            pub Extensions: Extensions
         }
 
             #[derive(Debug, Serialize, Deserialize, Clone)] 
+            #[allow(non_snake_case)]
             pub struct Limits {
                 pub Limit: Vec<Limit>
             }
     
                 #[derive(Debug, Serialize, Deserialize, Clone)]
+                #[allow(non_snake_case)]
                 pub struct Limit {
                     pub Type: String,
                     pub MeasurementType: String,
                     pub Unit: String,
                     pub Name: String,
                     pub Range: Option<String>,
-                    pub Reference: String
+                    //not present on every firmware
+                    pub Reference: Option<String>,
+                    #[serde(flatten)]
+                    pub Extensions: Extensions
                 }
 
     #[derive(Debug, Serialize, Deserialize, Clone)]            
+    #[allow(non_snake_case)]
     pub struct Reports {
         pub Report: Vec<Report>
     }
 
         #[derive(Debug, Serialize, Deserialize, Clone)]
+        #[allow(non_snake_case)]
         pub struct Report {
             pub ID: String,
-            pub PeakPim_dBm: String,
-            pub PeakPimPowerSetPoint_dBm: String,
+            //only present on exports that include PIM tests
+            pub PeakPim_dBm: Option<String>,
+            pub PeakPimPowerSetPoint_dBm: Option<String>,
             pub TestPassed: String,
-            pub Items: Items
+            pub Items: Items,
+            #[serde(flatten)]
+            pub Extensions: Extensions
         }
 
             #[derive(Debug, Serialize, Deserialize, Clone)]
+            #[allow(non_snake_case)]
             pub struct Items {
                 pub Test: Vec<Test>
             }
 
                 #[derive(Debug, Serialize, Deserialize, Clone)]
+                #[allow(non_snake_case)]
                 pub struct Test {
                     pub ID: String,
                     pub StateID: String,
                     pub Time: String,
                     pub Calibrated: String,
                     pub Assets: Assets,
+                    #[serde(default)]
                     pub Tags: Vec<Tag>,
-                    pub Results: Results
+                    //not present on every firmware
+                    pub Devices: Option<TestDevices>,
+                    pub Results: Results,
+                    #[serde(flatten)]
+                    pub Extensions: Extensions
                 }
 
                     #[derive(Debug, Serialize, Deserialize, Clone)]
+                    #[allow(non_snake_case)]
                     pub struct Assets {
                         #[serde(rename = "$value")]
                         pub Asset: Vec<String>
                     }
 
                     #[derive(Debug, Serialize, Deserialize, Clone)] 
+                    #[allow(non_snake_case)]
                         pub struct Tag {
                             pub Tag: String
                         }
 
                     #[derive(Debug, Serialize, Deserialize, Clone)] 
+                    #[allow(non_snake_case)]
                     pub struct TestDevices {
-                        pub Devices: Vec<TestDeviceDetails> 
+                        #[serde(default)]
+                        pub Device: Vec<TestDeviceDetails>
                     }
 
                         #[derive(Debug, Serialize, Deserialize, Clone)] 
+                        #[allow(non_snake_case)]
                         pub struct TestDeviceDetails {
                             pub ID: String
                         }
                     
                 #[derive(Debug, Serialize, Deserialize, Clone)] 
+                #[allow(non_snake_case)]
                 pub struct Results {
                     pub TestResult: TestResult
                 }
 
                     #[derive(Debug, Serialize, Deserialize, Clone)] 
+                    #[allow(non_snake_case)]
                     pub struct TestResult {
                         pub MeasurementType: String,
                         pub Unit: String,
@@ -138,4 +277,46 @@ pub struct Bundle {
                         pub Average: String,
                         pub Ripple: String,
                         pub Pass: String,
-                    }
\ No newline at end of file
+                        #[serde(flatten)]
+                        pub Extensions: Extensions
+                    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> Bundle {
+        raw_report_from_str(std::fs::read_to_string("fixtures/v1.0/Report.xml").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn reads_the_v1_0_fixture() {
+        let bundle = fixture();
+
+        assert!(bundle.is_tested_version());
+        assert!(bundle.unrecognised_elements().is_empty());
+        assert_eq!(bundle.Reports.Report[0].Items.Test.len(), 10);
+        assert_eq!(bundle.assets().first().map(|e| e.as_str()), Some("Test_001_DTF.csv"));
+        assert_eq!(bundle.assets().len(), 10);
+    }
+
+    #[test]
+    fn keeps_unknown_elements_and_attributes() {
+        let xml = std::fs::read_to_string("fixtures/v1.0/Report.xml").unwrap()
+            .replacen("<Device>", "<Device Firmware=\"3.0\">", 1)
+            .replacen("<Model>", "<Antenna><Port>2</Port></Antenna><Model>", 1);
+        let bundle = raw_report_from_str(xml).unwrap();
+
+        assert_eq!(bundle.unrecognised_elements(), vec!["Devices/Device/Antenna", "Devices/Device/Firmware"]);
+        let device = &bundle.Devices.Device[0];
+        assert!(matches!(device.Extensions.get("Firmware"), Some(Extension::Text(e)) if e == "3.0"));
+        assert!(matches!(device.Extensions.get("Antenna"), Some(Extension::Node(e)) if e.contains_key("Port")));
+    }
+
+    #[test]
+    fn fields_only_accept_the_formats_seen_in_exports() {
+        assert!(parse_time("2023-03-20 13:30").is_ok());
+        assert!(parse_time("2023-03-20T13:30:00").is_err());
+        assert_eq!(parse_pair::<f64>("26.90:1.325"), Ok((26.9, 1.325)));
+        assert!(parse_pair::<f64>("26.90;1.325").is_err());
+    }
+}
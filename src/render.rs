@@ -0,0 +1,205 @@
+use std::{fs, path::{Path, PathBuf}, str::FromStr};
+
+use plotters::{coord::Shift, prelude::*};
+
+use crate::{ipa_report::Report, trace::Trace};
+
+const SIZE: (u32, u32) = (1000, 500);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Svg,
+    Png,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.trim().to_ascii_lowercase().as_str() {
+            "svg" => Ok(Format::Svg),
+            "png" => Ok(Format::Png),
+            _ => Err(format!("Unknown image format: {}", input)),
+        }
+    }
+}
+
+struct ChartSpec<'a> {
+    title: String,
+    x_label: String,
+    y_label: String,
+    x_range: (f64, f64),
+    trace: &'a Trace,
+    limit: Option<f64>,
+    ///x position and label of the marker annotation
+    marker: Option<(f64, String)>,
+}
+
+///Writes a DTF and an RL chart for the cable into `dir`, returning the paths written.
+pub fn render_report(report: &Report, dir: &Path, format: Format) -> Result<Vec<PathBuf>, String> {
+
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let mut written: Vec<PathBuf> = Vec::new();
+
+    if let Some(spec) = dtf_spec(report) {
+        written.push(render(&spec, &dir.join(file_name(&report.tag, "DTF", format)), format)?);
+    }
+
+    if let Some(spec) = rl_spec(report) {
+        written.push(render(&spec, &dir.join(file_name(&report.tag, "RL", format)), format)?);
+    }
+
+    Ok(written)
+}
+
+///The DTF (`dtf`) or RL (`rl`) chart for the cable as an SVG document, `None` when it has no such test.
+pub fn render_svg(report: &Report, test: &str) -> Option<Result<String, String>> {
+
+    let spec = match test.to_ascii_lowercase().as_str() {
+        "dtf" => dtf_spec(report)?,
+        "rl" => rl_spec(report)?,
+        _ => return None,
+    };
+
+    let mut svg = String::new();
+    let drawn = draw(SVGBackend::with_string(&mut svg, SIZE).into_drawing_area(), &spec);
+    Some(drawn.map(|_| svg))
+}
+
+//a trace with no points has nothing to scale the chart to, so it gets no chart
+fn dtf_spec(report: &Report) -> Option<ChartSpec<'_>> {
+    let trace = report.dtf_trace.as_ref().filter(|trace| !trace.points.is_empty())?;
+    let (result, state) = (report.dtf_result.as_ref()?, report.dtf_state.as_ref()?);
+    Some(ChartSpec { 
+        title: format!("{} - DTF, {}-{} MHz", report.tag, state.rx_khz.0 / 1000, state.rx_khz.1 / 1000), 
+        x_label: "Distance (m)".to_string(), 
+        y_label: result.unit.to_string(), 
+        x_range: (0.0, state.limit_distance as f64), 
+        trace, 
+        limit: state.limit.value_in(result.unit), 
+        marker: report.dtf_marker.map(|marker| (marker, format!("Marker {:.2} m, worst {:.2} at {:.2} m", marker, result.max.1, result.max.0))),
+    })
+}
+
+fn rl_spec(report: &Report) -> Option<ChartSpec<'_>> {
+    let trace = report.rl_trace.as_ref().filter(|trace| !trace.points.is_empty())?;
+    let (result, state) = (report.rl_result.as_ref()?, report.rl_state.as_ref()?);
+    let x_range = match state.rx_khz {
+        Some((start, stop)) => (start as f64 / 1000.0, stop as f64 / 1000.0),
+        None => (trace.points.first().map_or(0.0, |e| e.0), trace.points.last().map_or(0.0, |e| e.0)),
+    };
+    Some(ChartSpec { 
+        title: format!("{} - Return Loss", report.tag), 
+        x_label: "Frequency (MHz)".to_string(), 
+        y_label: result.unit.to_string(), 
+        x_range, 
+        trace, 
+        limit: state.limit.value_in(result.unit), 
+        marker: Some((result.max.0, format!("Worst {:.2} dB at {:.1} MHz", result.max.1, result.max.0))),
+    })
+}
+
+///Characters other than letters, digits, `-` and `_` are written as `~` and their UTF-8 bytes in hex,
+///so tags that differ only in them ("A/1" and "A_1") still get a file each.
+fn file_name(tag: &str, test: &str, format: Format) -> String {
+    let tag: String = tag.chars().map(|c| match c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+        true => c.to_string(),
+        false => c.to_string().bytes().map(|byte| format!("~{:02X}", byte)).collect(),
+    }).collect();
+    match format {
+        Format::Svg => format!("{}_{}.svg", tag, test),
+        Format::Png => format!("{}_{}.png", tag, test),
+    }
+}
+
+fn render(spec: &ChartSpec, path: &Path, format: Format) -> Result<PathBuf, String> {
+    match format {
+        Format::Svg => draw(SVGBackend::new(path, SIZE).into_drawing_area(), spec)?,
+        Format::Png => draw(BitMapBackend::new(path, SIZE).into_drawing_area(), spec)?,
+    }
+    Ok(path.to_path_buf())
+}
+
+fn draw<DB: DrawingBackend>(root: DrawingArea<DB, Shift>, spec: &ChartSpec) -> Result<(), String> {
+
+    let error = |e: DrawingAreaErrorKind<DB::ErrorType>| e.to_string();
+
+    let mut y_min = spec.trace.points.iter().map(|point| point.1).fold(f64::MAX, f64::min);
+    let mut y_max = spec.trace.points.iter().map(|point| point.1).fold(f64::MIN, f64::max);
+    if let Some(limit) = spec.limit {
+        y_min = y_min.min(limit);
+        y_max = y_max.max(limit);
+    }
+    let padding = ((y_max - y_min) * 0.05).max(0.001);
+
+    root.fill(&WHITE).map_err(error)?;
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption(&spec.title, ("sans-serif", 22))
+        .margin(15)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(spec.x_range.0..spec.x_range.1, (y_min - padding)..(y_max + padding))
+        .map_err(error)?;
+
+    chart.configure_mesh()
+        .x_desc(&spec.x_label)
+        .y_desc(&spec.y_label)
+        .draw()
+        .map_err(error)?;
+
+    chart.draw_series(LineSeries::new(spec.trace.points.iter().cloned(), &BLUE))
+        .map_err(error)?
+        .label(spec.y_label.as_str())
+        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLUE));
+
+    if let Some(limit) = spec.limit {
+        chart.draw_series(DashedLineSeries::new(vec![(spec.x_range.0, limit), (spec.x_range.1, limit)], 8, 4, RED.stroke_width(2)))
+            .map_err(error)?
+            .label(format!("Limit {:.2}", limit))
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], RED.stroke_width(2)));
+    }
+
+    if let Some((x, label)) = &spec.marker {
+        chart.draw_series(LineSeries::new(vec![(*x, y_min - padding), (*x, y_max + padding)], RGBColor(230, 140, 0)))
+            .map_err(error)?
+            .label(label.as_str())
+            .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], RGBColor(230, 140, 0)));
+    }
+
+    chart.configure_series_labels()
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()
+        .map_err(error)?;
+
+    root.present().map_err(error)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Tz;
+
+    use super::*;
+    use crate::ipa_report;
+
+    #[test]
+    fn an_empty_trace_gets_no_chart() {
+        let mut cable = ipa_report::fixture_report(Tz::UTC).0.reports.remove(0);
+        assert!(render_svg(&cable, "dtf").is_some_and(|svg| svg.is_ok()));
+
+        cable.dtf_trace = Some(Trace { points: Vec::new() });
+        assert!(render_svg(&cable, "dtf").is_none());
+        assert!(render_svg(&cable, "rl").is_some());
+    }
+
+    #[test]
+    fn tags_differing_in_unsafe_characters_get_their_own_file() {
+        assert_eq!(file_name("F-L29-9", "DTF", Format::Svg), "F-L29-9_DTF.svg");
+        assert_eq!(file_name("A/1", "DTF", Format::Svg), "A~2F1_DTF.svg");
+        assert_eq!(file_name("A_1", "DTF", Format::Svg), "A_1_DTF.svg");
+        assert_eq!(file_name("A~2F1", "RL", Format::Png), "A~7E2F1_RL.png");
+    }
+}
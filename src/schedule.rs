@@ -0,0 +1,109 @@
+use serde_derive::Serialize;
+
+use crate::{ipa_report::{Report, SweepReport}};
+
+///A cable the site is expected to have tested.
+#[derive(Debug, Clone)]
+pub struct ScheduledCable {
+    pub tag: String,
+    ///expected length in metres, when the schedule has one
+    pub length: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    pub cables: Vec<ScheduledCable>,
+}
+
+impl Schedule {
+
+    ///Reads a cable schedule CSV. The header needs a `tag` column, a `length` column (metres) is
+    ///optional and anything else is ignored.
+    pub fn from_csv(raw_csv: &str) -> Result<Schedule, String> {
+
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).flexible(true).from_reader(raw_csv.as_bytes());
+
+        let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+        let column = |name: &str| headers.iter().position(|e| e.eq_ignore_ascii_case(name));
+
+        let tag_column = column("tag").ok_or("Cable schedule has no tag column")?;
+        let length_column = column("length");
+
+        let mut cables: Vec<ScheduledCable> = Vec::new();
+
+        for (i, record) in reader.records().enumerate() {
+            let record = record.map_err(|e| e.to_string())?;
+
+            let tag = record.get(tag_column).unwrap_or("");
+            if tag.is_empty() {
+                continue;
+            }
+
+            let length = match length_column.and_then(|column| record.get(column)).filter(|e| !e.is_empty()) {
+                Some(e) => Some(e.trim_end_matches('m').parse::<f64>().map_err(|_| format!("Bad length {} for {} on line {}", e, tag, i + 2))?),
+                None => None,
+            };
+
+            cables.push(ScheduledCable { tag: tag.to_string(), length });
+        }
+
+        Ok(Schedule { cables })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    ///tested and passed, at the scheduled length
+    Complete,
+    Failed,
+    ///passed, but the marker is outside the tolerance of the scheduled length
+    LengthMismatch,
+    ///scheduled but not in the export
+    Missing,
+    ///in the export but not on the schedule
+    Unexpected,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Reconciliation {
+    pub tag: String,
+    pub status: Status,
+    pub expected_length: Option<f64>,
+    pub measured_length: Option<f64>,
+}
+
+///Matches the cables in a report to the schedule by tag, ignoring case. When a tag was swept more
+///than once the last attempt counts. Results are in schedule order, with unexpected cables last.
+pub fn reconcile(schedule: &Schedule, report: &SweepReport, length_tolerance: f64) -> Vec<Reconciliation> {
+
+    let find = |tag: &str| -> Option<&Report> {
+        report.reports.iter().rev().find(|e| e.tag.eq_ignore_ascii_case(tag))
+    };
+
+    let mut results: Vec<Reconciliation> = schedule.cables.iter().map(|cable| {
+
+        let tested = find(&cable.tag);
+        let measured_length = tested.and_then(|e| e.dtf_marker);
+
+        let status = match tested {
+            None => Status::Missing,
+            Some(e) if e.passed() == Some(false) => Status::Failed,
+            Some(_) => match (cable.length, measured_length) {
+                (Some(expected), Some(measured)) if (measured - expected).abs() > length_tolerance => Status::LengthMismatch,
+                _ => Status::Complete,
+            },
+        };
+
+        Reconciliation { tag: cable.tag.clone(), status, expected_length: cable.length, measured_length }
+    }).collect();
+
+    for e in &report.reports {
+        if results.iter().any(|result| result.tag.eq_ignore_ascii_case(&e.tag)) {
+            continue;
+        }
+        results.push(Reconciliation { tag: e.tag.clone(), status: Status::Unexpected, expected_length: None, measured_length: e.dtf_marker });
+    }
+
+    results
+}
@@ -0,0 +1,233 @@
+use std::{net::IpAddr, panic::{self, AssertUnwindSafe}};
+
+use serde_derive::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{batch::IndexRow, export::{Certificate, CertificateRow}, ipa_report::{SweepReport, Warning}, render};
+
+const VIEWER: &str = include_str!("viewer.html");
+
+///One export served, or the reason it couldn't be loaded.
+pub struct Job {
+    pub row: IndexRow,
+    pub report: Option<SweepReport>,
+    pub warnings: Vec<Warning>,
+}
+
+#[derive(Serialize)]
+struct JobSummary<'a> {
+    id: usize,
+    #[serde(flatten)]
+    row: &'a IndexRow,
+}
+
+#[derive(Serialize)]
+struct JobDetail<'a> {
+    id: usize,
+    #[serde(flatten)]
+    row: &'a IndexRow,
+    certificate: Option<Certificate>,
+    warnings: &'a [Warning],
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+///Answers requests until the process is stopped. Everything is served from memory, the viewer
+///included, so nothing is fetched from the internet.
+pub fn serve(jobs: &[Job], bind: &str, port: u16, cal_interval: u32) -> Result<(), String> {
+
+    let server = Server::http((bind, port)).map_err(|e| format!("{}:{}: {}", bind, port, e))?;
+
+    for request in server.incoming_requests() {
+        let host = request.headers().iter().find(|header| header.field.equiv("Host")).map_or("", |header| header.value.as_str());
+
+        let response = match request.method() {
+            _ if !host_allowed(host, bind, port) => error(403, &format!("Unknown host {}", host)),
+            //a bug in one page shouldn't take the viewer down, the panic is still printed
+            Method::Get => panic::catch_unwind(AssertUnwindSafe(|| route(jobs, request.url(), cal_interval)))
+                .unwrap_or_else(|_| error(500, "Internal error")),
+            _ => error(405, "Only GET is supported"),
+        };
+        respond(request, response);
+    }
+
+    Ok(())
+}
+
+///Whether the Host header names the address being served, so a web page can't reach the server
+///through a name of its own that resolves here (DNS rebinding). Loopback addresses also answer to
+///localhost, and bound to every interface any IP address is let in, but still no names.
+fn host_allowed(host: &str, bind: &str, port: u16) -> bool {
+
+    let (name, given_port) = match host.strip_prefix('[') {
+        Some(rest) => match rest.split_once(']') {
+            Some((name, rest)) => (name, rest.strip_prefix(':')),
+            None => return false,
+        },
+        None => match host.rsplit_once(':') {
+            Some((name, given_port)) => (name, Some(given_port)),
+            None => (host, None),
+        },
+    };
+
+    //no port means the default one
+    if given_port.map_or(port != 80, |given_port| given_port.parse::<u16>() != Ok(port)) {
+        return false;
+    }
+
+    let bind = bind.trim_start_matches('[').trim_end_matches(']');
+    let loopback = |name: &str| name.eq_ignore_ascii_case("localhost") || name.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback());
+
+    match bind.parse::<IpAddr>() {
+        Ok(ip) if ip.is_unspecified() => name.parse::<IpAddr>().is_ok() || loopback(name),
+        _ => name.eq_ignore_ascii_case(bind) || (loopback(bind) && loopback(name)),
+    }
+}
+
+struct Reply {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+fn respond(request: Request, reply: Reply) {
+    let header = Header::from_bytes(&b"Content-Type"[..], reply.content_type.as_bytes()).unwrap();
+    //the client going away mid response isn't the server's problem
+    let _ = request.respond(Response::from_data(reply.body).with_status_code(reply.status).with_header(header));
+}
+
+fn json<T: serde::Serialize>(value: &T) -> Reply {
+    Reply { status: 200, content_type: "application/json", body: serde_json::to_vec_pretty(value).unwrap() }
+}
+
+fn error(status: u16, message: &str) -> Reply {
+    Reply { status, content_type: "application/json", body: serde_json::to_vec_pretty(&ErrorBody { error: message.to_string() }).unwrap() }
+}
+
+fn route(jobs: &[Job], url: &str, cal_interval: u32) -> Reply {
+
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments: Vec<String> = path.split('/').filter(|e| !e.is_empty()).map(percent_decode).collect();
+    let segments: Vec<&str> = segments.iter().map(|e| e.as_str()).collect();
+
+    let job = |id: &str| -> Result<(usize, &Job), Reply> {
+        id.parse::<usize>().ok()
+            .and_then(|id| Some((id, jobs.get(id)?)))
+            .ok_or_else(|| error(404, &format!("No job {}", id)))
+    };
+
+    let reply = match segments.as_slice() {
+        [] => Ok(Reply { status: 200, content_type: "text/html; charset=utf-8", body: VIEWER.as_bytes().to_vec() }),
+        ["api", "jobs"] => Ok(json(&jobs.iter().enumerate().map(|(id, job)| JobSummary { id, row: &job.row }).collect::<Vec<JobSummary>>())),
+        ["api", "jobs", id] => job(id).map(|(id, job)| json(&JobDetail {
+            id,
+            row: &job.row,
+            certificate: job.report.as_ref().map(|report| Certificate::from_sweep_report(report, cal_interval)),
+            warnings: &job.warnings,
+        })),
+        ["api", "jobs", id, "cables"] => job(id).map(|(_, job)| json(&cables(job, query, cal_interval))),
+        ["api", "jobs", id, "warnings"] => job(id).map(|(_, job)| json(&job.warnings)),
+        ["api", "jobs", id, "plots", tag, file] => job(id).and_then(|(_, job)| plot(job, tag, file)),
+        _ => Err(error(404, &format!("Nothing at {}", path))),
+    };
+
+    reply.unwrap_or_else(|e| e)
+}
+
+///The job's cables, filtered by `tag` (any part of it, ignoring case) and `pass` (true or false).
+fn cables(job: &Job, query: &str, cal_interval: u32) -> Vec<CertificateRow> {
+
+    let Some(report) = &job.report else {
+        return Vec::new();
+    };
+
+    let parameters: Vec<(String, String)> = query.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (query_decode(key), query_decode(value)))
+        .collect();
+    let parameter = |name: &str| parameters.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str()).filter(|e| !e.is_empty());
+
+    let tag = parameter("tag").map(|e| e.to_lowercase());
+    let pass = parameter("pass").and_then(|e| e.parse::<bool>().ok());
+
+    Certificate::from_sweep_report(report, cal_interval).cables.into_iter()
+        .zip(&report.reports)
+        .filter(|(row, _)| tag.as_ref().is_none_or(|tag| row.tag.to_lowercase().contains(tag)))
+        .filter(|(_, report)| pass.is_none_or(|pass| report.passed() == Some(pass)))
+        .map(|(row, _)| row)
+        .collect()
+}
+
+///`file` is `dtf.svg` or `rl.svg`.
+fn plot(job: &Job, tag: &str, file: &str) -> Result<Reply, Reply> {
+
+    let report = job.report.as_ref()
+        .and_then(|report| report.reports.iter().rev().find(|report| report.tag == tag))
+        .ok_or_else(|| error(404, &format!("No cable {}", tag)))?;
+    let test = file.strip_suffix(".svg").ok_or_else(|| error(404, "Plots are .svg"))?;
+
+    match render::render_svg(report, test) {
+        Some(Ok(svg)) => Ok(Reply { status: 200, content_type: "image/svg+xml", body: svg.into_bytes() }),
+        Some(Err(e)) => Err(error(500, &e)),
+        None => Err(error(404, &format!("No {} trace for {}", test, tag))),
+    }
+}
+
+///Undoes a query string's escapes, where forms and URLSearchParams write spaces as `+`.
+fn query_decode(input: &str) -> String {
+    percent_decode(&input.replace('+', " "))
+}
+
+///Undoes the %XX escapes browsers put in URLs.
+fn percent_decode(input: &str) -> String {
+
+    let bytes = input.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            },
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            },
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_strings_decode_plus_as_a_space() {
+        assert_eq!(query_decode("ANT+1%2BA"), "ANT 1+A");
+        assert_eq!(percent_decode("ANT+1"), "ANT+1");
+    }
+
+    #[test]
+    fn only_the_bound_address_is_answered() {
+        assert!(host_allowed("127.0.0.1:8080", "127.0.0.1", 8080));
+        assert!(host_allowed("localhost:8080", "127.0.0.1", 8080));
+        assert!(host_allowed("192.168.1.20:8080", "0.0.0.0", 8080));
+        assert!(host_allowed("[::1]:8080", "::1", 8080));
+
+        assert!(!host_allowed("attacker.example:8080", "127.0.0.1", 8080));
+        assert!(!host_allowed("attacker.example:8080", "0.0.0.0", 8080));
+        assert!(!host_allowed("127.0.0.1:9090", "127.0.0.1", 8080));
+        assert!(!host_allowed("", "127.0.0.1", 8080));
+    }
+}
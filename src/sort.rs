@@ -0,0 +1,180 @@
+use std::{cmp::Ordering, str::FromStr};
+
+use crate::ipa_report::Report;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Tag,
+    Length,
+    Vswr,
+    Rl,
+    ///time of the first test on the tag
+    Time,
+    ///headroom below the limit, see `Report::margin`
+    Margin,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.trim().to_ascii_lowercase().as_str() {
+            "tag" => Ok(SortKey::Tag),
+            "length" => Ok(SortKey::Length),
+            "vswr" => Ok(SortKey::Vswr),
+            "rl" => Ok(SortKey::Rl),
+            "time" => Ok(SortKey::Time),
+            "margin" => Ok(SortKey::Margin),
+            _ => Err(format!("Unknown sort key: {}, expected tag, length, vswr, rl, time or margin", input)),
+        }
+    }
+}
+
+///Comma separated sort keys, later keys break ties in earlier ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKeys(pub Vec<SortKey>);
+
+impl FromStr for SortKeys {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        input.split(',').map(|key| key.parse()).collect::<Result<Vec<SortKey>, String>>().map(SortKeys)
+    }
+}
+
+///Sorts cables by `keys` in turn, keeping report order for ties. Cables missing a value always go
+///last, whichever direction is asked for.
+pub fn sort_reports(reports: &mut [Report], keys: &[SortKey], descending: bool) {
+    reports.sort_by(|a, b| {
+        keys.iter()
+            .map(|key| compare(a, b, *key, descending))
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+fn compare(a: &Report, b: &Report, key: SortKey, descending: bool) -> Ordering {
+
+    let ordering = match key {
+        SortKey::Tag => Some(natural_cmp(&a.tag, &b.tag)),
+        SortKey::Length => compare_values(a.dtf_marker, b.dtf_marker),
+        SortKey::Vswr => compare_values(a.dtf_result.as_ref().map(|e| e.max.1), b.dtf_result.as_ref().map(|e| e.max.1)),
+        SortKey::Rl => compare_values(a.rl_result.as_ref().map(|e| e.max.1), b.rl_result.as_ref().map(|e| e.max.1)),
+        SortKey::Time => match (a.time(), b.time()) {
+            (Some(a), Some(b)) => Some(a.cmp(&b)),
+            _ => None,
+        },
+        SortKey::Margin => compare_values(a.margin(), b.margin()),
+    };
+
+    match ordering {
+        Some(ordering) if descending => ordering.reverse(),
+        Some(ordering) => ordering,
+        None => missing_last(has_value(a, key), has_value(b, key)),
+    }
+}
+
+fn compare_values(a: Option<f64>, b: Option<f64>) -> Option<Ordering> {
+    Some(a?.total_cmp(&b?))
+}
+
+fn has_value(report: &Report, key: SortKey) -> bool {
+    match key {
+        SortKey::Tag => true,
+        SortKey::Length => report.dtf_marker.is_some(),
+        SortKey::Vswr => report.dtf_result.is_some(),
+        SortKey::Rl => report.rl_result.is_some(),
+        SortKey::Time => report.time().is_some(),
+        SortKey::Margin => report.margin().is_some(),
+    }
+}
+
+fn missing_last(a: bool, b: bool) -> Ordering {
+    b.cmp(&a)
+}
+
+///Compares tags the way people read them: runs of digits by value, so F-L2-9 comes before F-L2-10,
+///and everything else ignoring case.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+
+    let (mut a_chars, mut b_chars) = (a.chars().peekable(), b.chars().peekable());
+
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_digits(&mut a_chars);
+                let y = take_digits(&mut b_chars);
+                let (x_trimmed, y_trimmed) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+
+                let ordering = x_trimmed.len().cmp(&y_trimmed.len()).then_with(|| x_trimmed.cmp(y_trimmed));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            },
+            (Some(x), Some(y)) => {
+                let ordering = x.to_ascii_lowercase().cmp(&y.to_ascii_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            },
+        }
+    }
+}
+
+fn take_digits(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        digits.push(c);
+    }
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Tz;
+
+    use super::*;
+    use crate::ipa_report::fixture_report;
+
+    fn tags(reports: &[Report]) -> Vec<&str> {
+        reports.iter().map(|e| e.tag.as_str()).collect()
+    }
+
+    #[test]
+    fn numbers_in_tags_sort_by_value() {
+        let mut tags = vec!["F-L2-10", "f-l2-9", "F-L2-009a", "F-L10-1", "F-L2-9"];
+        tags.sort_by(|a, b| natural_cmp(a, b));
+
+        assert_eq!(tags, ["F-L2-9", "f-l2-9", "F-L2-009a", "F-L2-10", "F-L10-1"]);
+    }
+
+    #[test]
+    fn missing_values_go_last_either_way() {
+        let mut reports = fixture_report(Tz::UTC).0.reports;
+        reports[1].dtf_marker = None;
+
+        sort_reports(&mut reports, &[SortKey::Length], false);
+        assert_eq!(reports.last().unwrap().tag, "F-L29-227");
+
+        sort_reports(&mut reports, &[SortKey::Length], true);
+        assert_eq!(reports.last().unwrap().tag, "F-L29-227");
+    }
+
+    #[test]
+    fn later_keys_break_ties_in_the_same_direction() {
+        let mut reports = fixture_report(Tz::UTC).0.reports;
+        for report in reports.iter_mut() {
+            report.dtf_marker = Some(10.0);
+        }
+        reports[4].dtf_marker = Some(20.0);
+
+        sort_reports(&mut reports, &[SortKey::Length, SortKey::Tag], true);
+
+        assert_eq!(tags(&reports), ["F-L29-10", "F-L29-228", "F-L29-227", "F-L29-226", "F-L29-9"]);
+    }
+}
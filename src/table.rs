@@ -0,0 +1,270 @@
+use std::str::FromStr;
+
+use math::round;
+use crate::color::Colorize;
+
+use crate::{ipa_report::{Report, SweepReport, TestResult}, metrics::{self, Unit}};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Column {
+    Tag,
+    Length,
+    Vswr,
+    ///distance of the worst DTF point
+    VswrAt,
+    Rl,
+    Pass,
+    Time,
+    ///serial numbers of the devices the cable was tested with
+    Device,
+    Margin,
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.trim().to_ascii_lowercase().as_str() {
+            "tag" => Ok(Column::Tag),
+            "length" => Ok(Column::Length),
+            "vswr" | "dtf" => Ok(Column::Vswr),
+            "vswr_at" => Ok(Column::VswrAt),
+            "rl" => Ok(Column::Rl),
+            "pass" => Ok(Column::Pass),
+            "time" => Ok(Column::Time),
+            "device" => Ok(Column::Device),
+            "margin" => Ok(Column::Margin),
+            _ => Err(format!("Unknown column: {}, expected tag, length, vswr, vswr_at, rl, pass, time, device or margin", input)),
+        }
+    }
+}
+
+impl Column {
+    fn header(&self, unit: Option<Unit>) -> String {
+        match (self, unit) {
+            (Column::Tag, _) => "Tag".to_string(),
+            (Column::Length, _) => "Length (m)".to_string(),
+            (Column::Vswr, None) => "VSWR".to_string(),
+            (Column::Vswr, Some(unit)) => format!("DTF ({})", unit),
+            (Column::VswrAt, _) => "Worst at (m)".to_string(),
+            (Column::Rl, None) => "RL (dB)".to_string(),
+            (Column::Rl, Some(unit)) => format!("RL ({})", unit),
+            (Column::Pass, _) => "Pass".to_string(),
+            (Column::Time, _) => "Time".to_string(),
+            (Column::Device, _) => "Device".to_string(),
+            (Column::Margin, _) => "Margin (dB)".to_string(),
+        }
+    }
+
+    fn cell(&self, input: &SweepReport, report: &Report, unit: Option<Unit>) -> Cell {
+        match self {
+            Column::Tag => Cell::plain(report.tag.clone()),
+            Column::Length => Cell::value(report.dtf_marker.map(|e| round::half_away_from_zero(e, 2).to_string())),
+            Column::Vswr => Cell::value(report.dtf_result.as_ref().map(|e| result_value(e, unit))),
+            Column::VswrAt => Cell::value(report.dtf_result.as_ref().map(|e| round::half_away_from_zero(e.max.0, 2).to_string())),
+            Column::Rl => Cell::value(report.rl_result.as_ref().map(|e| result_value(e, unit))),
+            Column::Pass => match report.passed() {
+                Some(true) => Cell { text: "Pass".to_string(), tone: Tone::Good },
+                Some(false) => Cell { text: "Fail".to_string(), tone: Tone::Bad },
+                None => Cell::value(None),
+            },
+            Column::Time => Cell::value(report.time().map(|e| e.format("%Y-%m-%d %H:%M %Z").to_string())),
+            Column::Device => Cell::plain(input.devices_for(report).iter().map(|e| e.serial_number.as_str()).collect::<Vec<&str>>().join("/")),
+            Column::Margin => match report.margin() {
+                Some(e) if e < 0.0 => Cell { text: format!("{:.2}", e), tone: Tone::Bad },
+                e => Cell::value(e.map(|e| format!("{:.2}", e))),
+            },
+        }
+    }
+}
+
+///Comma separated columns, in the order they are shown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Columns(pub Vec<Column>);
+
+impl FromStr for Columns {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        input.split(',').map(|column| column.parse()).collect::<Result<Vec<Column>, String>>().map(Columns)
+    }
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Columns(vec![Column::Tag, Column::Length, Column::Vswr, Column::Rl])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    ///padded columns, coloured for the terminal
+    Plain,
+    ///a GitHub flavoured Markdown table, uncoloured
+    Markdown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tone {
+    Normal,
+    Good,
+    Bad,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cell {
+    pub text: String,
+    pub tone: Tone,
+}
+
+impl Cell {
+    pub fn plain(text: String) -> Cell {
+        Cell { text, tone: Tone::Normal }
+    }
+
+    ///A value that may be missing, shown as a red N/A.
+    fn value(text: Option<String>) -> Cell {
+        match text {
+            Some(text) => Cell::plain(text),
+            None => Cell { text: "N/A".to_string(), tone: Tone::Bad },
+        }
+    }
+}
+
+pub struct Table {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+
+    ///One row per cable, in report order.
+    pub fn from_sweep_report(input: &SweepReport, columns: &[Column], unit: Option<Unit>) -> Table {
+        Table { 
+            headers: columns.iter().map(|column| column.header(unit)).collect(), 
+            rows: input.reports.iter().map(|report| columns.iter().map(|column| column.cell(input, report, unit)).collect()).collect(),
+        }
+    }
+
+    ///Each column is as wide as its widest cell or header.
+    pub fn widths(&self) -> Vec<usize> {
+        let rows: Vec<Vec<String>> = self.rows.iter().map(|row| row.iter().map(|cell| cell.text.clone()).collect()).collect();
+        widths(&self.headers, &rows)
+    }
+
+    pub fn render(&self, style: Style) -> Vec<String> {
+        match style {
+            Style::Plain => self.render_plain(),
+            Style::Markdown => self.render_markdown(),
+        }
+    }
+
+    fn render_plain(&self) -> Vec<String> {
+
+        let widths = self.widths();
+        let mut lines: Vec<String> = Vec::new();
+
+        //pad before colouring, the escape codes would throw out the widths
+        lines.push(self.headers.iter().zip(&widths)
+            .map(|(header, width)| format!("{: <width$}", header).green().to_string())
+            .collect::<Vec<String>>().join("  ").trim_end().to_string());
+
+        for row in &self.rows {
+            lines.push(row.iter().zip(&widths)
+                .map(|(cell, width)| {
+                    let text = format!("{: <width$}", cell.text);
+                    match cell.tone {
+                        Tone::Normal => text,
+                        Tone::Good => text.green().to_string(),
+                        Tone::Bad => text.red().to_string(),
+                    }
+                })
+                .collect::<Vec<String>>().join("  ").trim_end().to_string());
+        }
+
+        lines
+    }
+
+    fn render_markdown(&self) -> Vec<String> {
+
+        //escape first, the widths have to count the backslashes
+        let escape = |text: &str| text.replace('|', "\\|");
+        let headers: Vec<String> = self.headers.iter().map(|e| escape(e)).collect();
+        let rows: Vec<Vec<String>> = self.rows.iter().map(|row| row.iter().map(|cell| escape(&cell.text)).collect()).collect();
+
+        let widths = widths(&headers, &rows);
+        let line = |cells: &[String]| format!("| {} |", cells.iter().zip(&widths).map(|(cell, width)| format!("{: <width$}", cell)).collect::<Vec<String>>().join(" | "));
+
+        let mut lines: Vec<String> = Vec::new();
+        lines.push(line(&headers));
+        lines.push(format!("|{}|", widths.iter().map(|width| "-".repeat(width + 2)).collect::<Vec<String>>().join("|")));
+
+        for row in &rows {
+            lines.push(line(row));
+        }
+
+        lines
+    }
+}
+
+fn widths(headers: &[String], rows: &[Vec<String>]) -> Vec<usize> {
+    headers.iter().enumerate().map(|(i, header)| {
+        rows.iter()
+            .filter_map(|row| row.get(i))
+            .map(|cell| cell.chars().count())
+            .chain([header.chars().count()])
+            .max()
+            .unwrap_or(0)
+    }).collect()
+}
+
+///The worst point of a result, converted to `unit` when one was asked for.
+pub fn format_result(result: &TestResult, unit: Option<Unit>) -> String {
+    match unit {
+        Some(unit) => format!("{} {}", result_value(result, Some(unit)), unit),
+        None => result_value(result, None),
+    }
+}
+
+///As `format_result`, without the unit for when it is in the column header.
+fn result_value(result: &TestResult, unit: Option<Unit>) -> String {
+    match unit {
+        Some(unit) => metrics::format_value(metrics::convert(result.max.1, result.unit, unit), unit),
+        None => round::half_away_from_zero(result.max.1, 2).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Table {
+        Table {
+            headers: vec!["Tag".to_string(), "Length (m)".to_string()],
+            rows: vec![
+                vec![Cell::plain("F-L29-226".to_string()), Cell::plain("26.89".to_string())],
+                vec![Cell::plain("A|B".to_string()), Cell::value(None)],
+            ],
+        }
+    }
+
+    #[test]
+    fn columns_are_as_wide_as_their_widest_cell_or_header() {
+        assert_eq!(table().widths(), [9, 10]);
+    }
+
+    #[test]
+    fn markdown_escapes_pipes_and_stays_aligned() {
+        let mut table = table();
+        table.rows[1][0] = Cell::plain("F-L29-|||||".to_string());
+
+        let lines = table.render(Style::Markdown);
+
+        assert_eq!(lines, [
+            "| Tag              | Length (m) |",
+            "|------------------|------------|",
+            "| F-L29-226        | 26.89      |",
+            "| F-L29-\\|\\|\\|\\|\\| | N/A        |",
+        ]);
+    }
+}
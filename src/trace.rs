@@ -0,0 +1,73 @@
+///Number of header lines before the sweep data in an instrument CSV, the last of them holds the marker.
+const HEADER_LINES: usize = 14;
+
+///Sweep data from a test's CSV asset.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    ///(distance m, VSWR) for DTF, (frequency MHz, dB) for RL
+    pub points: Vec<(f64, f64)>,
+}
+
+impl Trace {
+
+    ///Reads the sweep data after the header lines and column titles. A CSV too short to hold them,
+    ///or a data row that isn't two numbers, is an error.
+    pub fn from_csv(raw_csv: &str) -> Result<Trace, String> {
+
+        let mut lines = raw_csv.lines().enumerate().skip(HEADER_LINES);
+        if lines.next().is_none() {
+            return Err(format!("CSV has {} lines, expected at least {} before the data", raw_csv.lines().count(), HEADER_LINES + 1));
+        }
+
+        let mut points: Vec<(f64, f64)> = Vec::new();
+
+        for (i, line) in lines.filter(|(_, line)| !line.trim().is_empty()) {
+            let fields: Vec<&str> = line.split(',').collect();
+
+            match (fields.first().map(|e| e.trim().parse()), fields.get(1).map(|e| e.trim().parse())) {
+                (Some(Ok(x)), Some(Ok(y))) => points.push((x, y)),
+                _ => return Err(format!("CSV line {} is not a data row: {}", i + 1, line)),
+            }
+        }
+
+        Ok(Trace { points })
+    }
+
+    ///Linearly interpolated value at `x`, None outside the trace.
+    pub fn value_at(&self, x: f64) -> Option<f64> {
+        let i = self.points.iter().position(|point| point.0 >= x)?;
+        let (x1, y1) = self.points[i];
+
+        if i == 0 || x1 == x {
+            return match x1 == x {
+                true => Some(y1),
+                false => None,
+            };
+        }
+
+        let (x0, y0) = self.points[i - 1];
+        Some(y0 + (y1 - y0) * (x - x0) / (x1 - x0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_fixture_trace() {
+        let trace = Trace::from_csv(&std::fs::read_to_string("fixtures/v1.0/Test_001_DTF.csv").unwrap()).unwrap();
+
+        assert_eq!(trace.points.len(), 401);
+        assert_eq!(trace.points[0], (0.0, 1.0094));
+    }
+
+    #[test]
+    fn short_or_malformed_csvs_are_errors() {
+        let header = "header\n".repeat(HEADER_LINES);
+
+        assert!(Trace::from_csv("Kaelus iVA Export\nModel,iVA-0627B\n").is_err());
+        assert!(Trace::from_csv(&format!("{}Distance (m),VSWR\n0.0,1.01\n0.1,oops\n", header)).is_err());
+        assert_eq!(Trace::from_csv(&format!("{}Distance (m),VSWR\n0.0,1.01\n", header)).unwrap().points, [(0.0, 1.01)]);
+    }
+}
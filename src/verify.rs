@@ -0,0 +1,118 @@
+use sha2::{Digest, Sha256};
+
+use crate::raw_ipa_report;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignatureStatus {
+    ///signature matches the documented digest
+    Valid,
+    ///no signature in the export
+    Unsigned,
+    ///signature is present but doesn't match the documented digest, most likely because the
+    ///instrument signs some other way, so nothing about the export was checked
+    UnknownScheme,
+}
+
+pub struct DeviceVerification {
+    pub serial_number: String,
+    pub model: String,
+    pub digest: String,
+    pub status: SignatureStatus,
+}
+
+pub fn sha256_hex(input: &[u8]) -> String {
+    Sha256::digest(input).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+///The documented digest: SHA-256 over the device block followed by every test and its result,
+///one line each, fields as written in Report.xml and joined with `|`:
+///
+///`SerialNumber|Model|ID|SWVersions|CalDate`, then for every test
+///`ID|StateID|Time|Calibrated|Tags|MeasurementType|Unit|P1|P2|Maximum|Minimum|Average|Ripple|Pass`
+///with the tags joined by `,`. Elements outside the known schema are added to the device line
+///as `name=value`, in name order.
+pub fn report_digest(input: &raw_ipa_report::Bundle, device: &raw_ipa_report::Device, details: &raw_ipa_report::DeviceDetails) -> String {
+
+    let mut device_line: Vec<String> = [&device.SerialNumber, &device.Model, &details.ID, &details.SWVersions, &details.CalDate]
+        .map(|e| e.to_string())
+        .to_vec();
+    for (name, value) in device.Extensions.iter().chain(&details.Extensions) {
+        device_line.push(format!("{}={}", name, serde_json::to_string(value).unwrap_or_default()));
+    }
+
+    let mut lines: Vec<String> = vec![device_line.join("|")];
+
+    for report in &input.Reports.Report {
+        for test in &report.Items.Test {
+            let result = &test.Results.TestResult;
+            let tags: Vec<&str> = test.Tags.iter().map(|tag| tag.Tag.as_str()).collect();
+            lines.push([
+                test.ID.as_str(), test.StateID.as_str(), test.Time.as_str(), test.Calibrated.as_str(), &tags.join(","),
+                result.MeasurementType.as_str(), result.Unit.as_str(), result.P1.as_str(), result.P2.as_str(),
+                result.Maximum.as_str(), result.Minimum.as_str(), result.Average.as_str(), result.Ripple.as_str(), result.Pass.as_str(),
+            ].join("|"));
+        }
+    }
+
+    sha256_hex(lines.join("\n").as_bytes())
+}
+
+pub fn verify_devices(input: &raw_ipa_report::Bundle) -> Vec<DeviceVerification> {
+
+    let mut results: Vec<DeviceVerification> = Vec::new();
+
+    for device in &input.Devices.Device {
+        for details in &device.Details.DeviceDetails {
+
+            let digest = report_digest(input, device, details);
+            let signature = details.Signature.clone().unwrap_or_default();
+            let signature = signature.trim();
+
+            //a signature that doesn't match can't be called tampered, it may just be the instrument's own scheme
+            let status = if signature.is_empty() {
+                SignatureStatus::Unsigned
+            } else if signature.eq_ignore_ascii_case(&digest) {
+                SignatureStatus::Valid
+            } else {
+                SignatureStatus::UnknownScheme
+            };
+
+            results.push(DeviceVerification {
+                serial_number: device.SerialNumber.clone(),
+                model: device.Model.clone(),
+                digest,
+                status
+            });
+        }
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle(xml: String) -> raw_ipa_report::Bundle {
+        raw_ipa_report::raw_report_from_str(xml).unwrap()
+    }
+
+    #[test]
+    fn the_digest_covers_the_results() {
+        let xml = std::fs::read_to_string("fixtures/v1.0/Report.xml").unwrap();
+
+        //the fixture carries the instrument's own signature
+        assert_eq!(verify_devices(&bundle(xml.clone()))[0].status, SignatureStatus::UnknownScheme);
+
+        let digest = verify_devices(&bundle(xml.clone()))[0].digest.clone();
+        let signed = xml.replacen("A1B2C3D4E5F6", &digest, 1);
+        assert_eq!(verify_devices(&bundle(signed.clone()))[0].status, SignatureStatus::Valid);
+
+        let edited = signed.replacen("<Pass>true</Pass>", "<Pass>false</Pass>", 1);
+        assert_ne!(edited, signed);
+        assert_eq!(verify_devices(&bundle(edited))[0].status, SignatureStatus::UnknownScheme);
+
+        let unsigned = xml.replacen("<Signature>A1B2C3D4E5F6</Signature>", "", 1);
+        assert_eq!(verify_devices(&bundle(unsigned))[0].status, SignatureStatus::Unsigned);
+    }
+}